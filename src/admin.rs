@@ -0,0 +1,191 @@
+// Signed administrative commands.
+//
+// An admin request carries four extra headers: the hex encoded public key
+// of the admin, a random nonce, a unix timestamp and an ed25519 signature
+// over the site's nym address, command, path, nonce, timestamp and body. The
+// server only runs admin commands signed for its own address by one of its
+// configured keys, inside a short time window and with a nonce it has not
+// seen before. Binding the address keeps a request for one site from being
+// played back to another site that trusts the same key.
+
+use ed25519_zebra::{Signature, SigningKey, VerificationKey};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::protocol::Request;
+use crate::signing;
+
+pub const ADMIN_COMMANDS: &[&str] = &["RELOAD", "STATS"];
+
+pub const HEADER_ADMIN_KEY: &str = "admin-key";
+pub const HEADER_ADMIN_NONCE: &str = "admin-nonce";
+pub const HEADER_ADMIN_TIMESTAMP: &str = "admin-timestamp";
+pub const HEADER_ADMIN_SIGNATURE: &str = "admin-signature";
+
+// Signed requests are accepted this many seconds either side of our clock
+const MAX_CLOCK_SKEW: u64 = 300;
+
+const SIGNING_CONTEXT: &[u8] = b"nymview-admin-v1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminAuthError {
+    NoAdminKeys,
+    MissingHeader(&'static str),
+    InvalidHeader(&'static str),
+    UnknownKey,
+    BadSignature,
+    Expired,
+    Replayed,
+}
+
+impl fmt::Display for AdminAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminAuthError::NoAdminKeys => write!(f, "administration is disabled on this server"),
+            AdminAuthError::MissingHeader(name) => write!(f, "admin header '{}' is missing", name),
+            AdminAuthError::InvalidHeader(name) => write!(f, "admin header '{}' is invalid", name),
+            AdminAuthError::UnknownKey => write!(f, "key is not an admin key of this server"),
+            AdminAuthError::BadSignature => write!(f, "signature does not verify"),
+            AdminAuthError::Expired => write!(f, "timestamp is outside the accepted window"),
+            AdminAuthError::Replayed => write!(f, "nonce was already used"),
+        }
+    }
+}
+
+impl std::error::Error for AdminAuthError {}
+
+pub fn is_admin_command(command: &str) -> bool {
+    ADMIN_COMMANDS.contains(&command)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Length prefixed fields so no two different requests sign the same bytes
+fn signing_payload(request: &Request, site: &str, nonce: &str, timestamp: &str) -> Vec<u8> {
+    let mut payload = SIGNING_CONTEXT.to_vec();
+    for field in [
+        site.as_bytes(),
+        request.command.as_bytes(),
+        request.path.as_bytes(),
+        nonce.as_bytes(),
+        timestamp.as_bytes(),
+        &request.body,
+    ] {
+        payload.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        payload.extend_from_slice(field);
+    }
+    payload
+}
+
+/// Adds the admin headers to a request for the site at `site` (its nym
+/// address), signed with `key`.
+pub fn sign_request(request: &mut Request, key: &SigningKey, site: &str) {
+    let nonce = hex::encode(rand::random::<[u8; 16]>());
+    let timestamp = unix_time().to_string();
+    let signature = key.sign(&signing_payload(request, site, &nonce, &timestamp));
+
+    request.headers.push((HEADER_ADMIN_KEY.to_string(), signing::public_key_hex(key)));
+    request.headers.push((HEADER_ADMIN_NONCE.to_string(), nonce));
+    request.headers.push((HEADER_ADMIN_TIMESTAMP.to_string(), timestamp));
+    request.headers.push((HEADER_ADMIN_SIGNATURE.to_string(), hex::encode(signature.to_bytes())));
+}
+
+pub fn parse_public_key(text: &str) -> Option<VerificationKey> {
+    let bytes: [u8; 32] = hex::decode(text.trim()).ok()?.try_into().ok()?;
+    VerificationKey::try_from(bytes).ok()
+}
+
+pub struct AdminAuth {
+    keys: Vec<VerificationKey>,
+    // Nym address of the site, requests must be signed for it
+    site: String,
+    // Nonces seen inside the clock skew window, with their timestamps
+    seen_nonces: Mutex<HashMap<String, u64>>,
+}
+
+impl AdminAuth {
+    pub fn new(keys: Vec<VerificationKey>, site: &str) -> Self {
+        Self {
+            keys,
+            site: site.to_string(),
+            seen_nonces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads hex encoded admin keys, one per line; `#` starts a comment.
+    /// A missing file means administration is disabled.
+    pub fn load(path: &Path, site: &str) -> std::io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut keys = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            match parse_public_key(line) {
+                Some(key) => keys.push(key),
+                None => eprintln!("Ignoring invalid admin key in {}: {}", path.display(), line),
+            }
+        }
+        Ok(Self::new(keys, site))
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn verify(&self, request: &Request) -> Result<(), AdminAuthError> {
+        if self.keys.is_empty() {
+            return Err(AdminAuthError::NoAdminKeys);
+        }
+
+        let header = |name: &'static str| request.header(name).ok_or(AdminAuthError::MissingHeader(name));
+        let key = parse_public_key(header(HEADER_ADMIN_KEY)?)
+            .ok_or(AdminAuthError::InvalidHeader(HEADER_ADMIN_KEY))?;
+        let nonce = header(HEADER_ADMIN_NONCE)?;
+        let timestamp_text = header(HEADER_ADMIN_TIMESTAMP)?;
+        let timestamp: u64 = timestamp_text
+            .parse()
+            .map_err(|_| AdminAuthError::InvalidHeader(HEADER_ADMIN_TIMESTAMP))?;
+        let signature_bytes: [u8; 64] = hex::decode(header(HEADER_ADMIN_SIGNATURE)?)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(AdminAuthError::InvalidHeader(HEADER_ADMIN_SIGNATURE))?;
+
+        if !self.keys.contains(&key) {
+            return Err(AdminAuthError::UnknownKey);
+        }
+        key.verify(
+            &Signature::from_bytes(&signature_bytes),
+            &signing_payload(request, &self.site, nonce, timestamp_text),
+        )
+        .map_err(|_| AdminAuthError::BadSignature)?;
+
+        let now = unix_time();
+        if now.abs_diff(timestamp) > MAX_CLOCK_SKEW {
+            return Err(AdminAuthError::Expired);
+        }
+
+        // Only signed, fresh requests get here, so the nonce table cannot be flooded
+        let mut seen = self.seen_nonces.lock().unwrap();
+        seen.retain(|_, seen_at| now.abs_diff(*seen_at) <= MAX_CLOCK_SKEW);
+        if seen.insert(nonce.to_string(), timestamp).is_some() {
+            return Err(AdminAuthError::Replayed);
+        }
+        Ok(())
+    }
+}
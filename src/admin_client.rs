@@ -0,0 +1,125 @@
+// Command line side of the admin commands: key generation and sending a
+// signed command to a running server over the mixnet.
+
+use ed25519_zebra::SigningKey;
+use nym_sdk::mixnet::{self, IncludedSurbs, MixnetMessageSender};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::admin;
+use crate::chunking::ChunkAssembler;
+use crate::pow::{self, Challenge};
+use crate::protocol::{Request, Response, Status};
+use crate::signing;
+
+const REPLY_SURBS: u32 = 10;
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Writes a new secret key to `key_file` and prints the public key to add
+/// to the server's `admin_keys`.
+pub fn generate_key(key_file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if key_file.exists() {
+        return Err(format!("{} already exists, refusing to overwrite it", key_file.display()).into());
+    }
+
+    let key = SigningKey::new(rand::thread_rng());
+    signing::write_secret_key(key_file, &key)?;
+
+    println!("Secret key written to {}", key_file.display());
+    println!("Add this line to the server's admin_keys file:");
+    println!("{}", signing::public_key_hex(&key));
+    Ok(())
+}
+
+pub async fn send_command(
+    server: &str,
+    command: &str,
+    path: &str,
+    key_file: &Path,
+) -> Result<Response, Box<dyn std::error::Error>> {
+    let command = command.to_ascii_uppercase();
+    if !admin::is_admin_command(&command) {
+        return Err(format!("'{}' is not an admin command", command).into());
+    }
+    let key = signing::parse_secret_key(&fs::read_to_string(key_file)?)
+        .ok_or_else(|| format!("{} does not hold a valid admin key", key_file.display()))?;
+    let server = server.trim().trim_start_matches("nym://");
+    let recipient: mixnet::Recipient = server.parse()?;
+
+    let mut request = Request::new(rand::random(), &command, path);
+    admin::sign_request(&mut request, &key, server);
+
+    println!("Connecting to Mixnet...");
+    let mut client = mixnet::MixnetClientBuilder::new_ephemeral()
+        .build()?
+        .connect_to_mixnet()
+        .await?;
+    println!("Sending {} to {}...", command, server);
+
+    let result = tokio::time::timeout(RESPONSE_TIMEOUT, exchange(&mut client, recipient, request)).await;
+    client.disconnect().await;
+    match result {
+        Ok(response) => response,
+        Err(_) => Err("No answer from the server".into()),
+    }
+}
+
+// Sends the request, solving a proof-of-work challenge once if the server is under load
+async fn exchange(
+    client: &mut mixnet::MixnetClient,
+    recipient: mixnet::Recipient,
+    mut request: Request,
+) -> Result<Response, Box<dyn std::error::Error>> {
+    loop {
+        client
+            .send_message(recipient, request.encode()?, IncludedSurbs::new(REPLY_SURBS))
+            .await?;
+        let response = wait_for_response(client, request.id).await?;
+
+        let already_solved = request.header(pow::HEADER_POW_NONCE).is_some();
+        let challenge = match Challenge::from_response(&response) {
+            Some(challenge) if response.status == Status::ChallengeRequired && !already_solved => challenge,
+            _ => return Ok(response),
+        };
+
+        println!("Server is under load, solving its challenge (difficulty {})...", challenge.difficulty);
+        let nonce = {
+            let (challenge, request) = (challenge.clone(), request.clone());
+            tokio::task::spawn_blocking(move || pow::solve(&challenge, &request)).await?
+        };
+        pow::attach(&mut request, &challenge, nonce);
+    }
+}
+
+async fn wait_for_response(
+    client: &mut mixnet::MixnetClient,
+    id: u64,
+) -> Result<Response, Box<dyn std::error::Error>> {
+    let mut assembler: Option<ChunkAssembler> = None;
+
+    while let Some(messages) = client.wait_for_messages().await {
+        for received in messages {
+            let response = match Response::decode(&received.message) {
+                Ok(response) if response.id == id => response,
+                Ok(_) => continue,
+                Err(e) => {
+                    eprintln!("Ignoring malformed message: {}", e);
+                    continue;
+                }
+            };
+
+            let mut current = match assembler.take() {
+                Some(current) => current,
+                None => ChunkAssembler::new(&response)?,
+            };
+            current.insert(response)?;
+            if current.is_complete() {
+                return Ok(current.finish(id)?);
+            }
+            assembler = Some(current);
+        }
+    }
+
+    Err("Mixnet connection closed".into())
+}
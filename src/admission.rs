@@ -0,0 +1,146 @@
+// Proof-of-work admission while the server is under load.
+//
+// Normally every request goes straight to the queue. Once the queue backs up
+// or the global rate limit is hit, requests must carry a solution to the
+// current challenge (see `pow`) until things have been calm for a while.
+// Unsolved requests are answered with 428 and the challenge to solve.
+
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::pow::{self, Challenge};
+use crate::protocol::Request;
+
+// Seeds are replaced this often; solutions for the previous one still count
+const SEED_LIFETIME: Duration = Duration::from_secs(120);
+
+// A solution admits its request plus a few resends of lost chunks
+pub const MAX_TICKET_USES: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdmissionConfig {
+    /// Leading zero bits a solution needs, 0 never asks for proof of work
+    pub difficulty: u8,
+    /// Queued requests at which the server counts as overloaded
+    pub queue_threshold: usize,
+    /// How long proof of work stays required after the last sign of overload
+    pub cool_down: Duration,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            difficulty: 20,
+            queue_threshold: 64,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+struct Seeds {
+    current: String,
+    previous: Option<String>,
+    created: Instant,
+    // (seed, request id, nonce) of accepted solutions
+    uses: HashMap<(String, u64, u64), u32>,
+}
+
+pub struct Admission {
+    config: AdmissionConfig,
+    overloaded_at: Mutex<Option<Instant>>,
+    seeds: Mutex<Seeds>,
+}
+
+impl Admission {
+    pub fn new(config: AdmissionConfig) -> Self {
+        Self {
+            config: AdmissionConfig {
+                difficulty: config.difficulty.min(pow::MAX_DIFFICULTY),
+                ..config
+            },
+            overloaded_at: Mutex::new(None),
+            seeds: Mutex::new(Seeds {
+                current: new_seed(),
+                previous: None,
+                created: Instant::now(),
+                uses: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn note_overload(&self) {
+        *self.overloaded_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub fn observe_queue(&self, queued: usize) {
+        if queued >= self.config.queue_threshold {
+            self.note_overload();
+        }
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.config.difficulty > 0
+            && self
+                .overloaded_at
+                .lock()
+                .unwrap()
+                .is_some_and(|at| at.elapsed() < self.config.cool_down)
+    }
+
+    /// Lets a message through unless proof of work is required and the
+    /// request doesn't carry a valid, unspent solution.
+    pub fn admit(&self, message: &[u8]) -> Result<(), Challenge> {
+        if !self.is_required() {
+            return Ok(());
+        }
+        // Left to the worker, which reports it as malformed
+        let Ok(request) = Request::decode(message) else {
+            return Ok(());
+        };
+
+        let mut seeds = self.seeds.lock().unwrap();
+        seeds.rotate();
+
+        let solved = request
+            .header(pow::HEADER_POW_SEED)
+            .filter(|seed| *seed == seeds.current || seeds.previous.as_deref() == Some(*seed))
+            .zip(request.header(pow::HEADER_POW_NONCE).and_then(|nonce| nonce.trim().parse().ok()))
+            .filter(|(seed, nonce)| {
+                let challenge = Challenge { seed: seed.to_string(), difficulty: self.config.difficulty };
+                pow::is_solution(&challenge, &request, *nonce)
+            });
+
+        if let Some((seed, nonce)) = solved {
+            let uses = seeds.uses.entry((seed.to_string(), request.id, nonce)).or_insert(0);
+            if *uses < MAX_TICKET_USES {
+                *uses += 1;
+                return Ok(());
+            }
+        }
+
+        Err(Challenge {
+            seed: seeds.current.clone(),
+            difficulty: self.config.difficulty,
+        })
+    }
+}
+
+impl Seeds {
+    fn rotate(&mut self) {
+        if self.created.elapsed() < SEED_LIFETIME {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.current, new_seed());
+        self.uses.retain(|(seed, _, _), _| *seed == previous);
+        self.previous = Some(previous);
+        self.created = Instant::now();
+    }
+}
+
+fn new_seed() -> String {
+    let mut seed = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut seed);
+    hex::encode(seed)
+}
@@ -0,0 +1,104 @@
+use egui::load::{Bytes, BytesLoadResult, BytesLoader, BytesPoll, LoadError};
+use egui::Context;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+enum AssetState {
+    Pending,
+    Ready { bytes: Arc<[u8]>, mime: Option<String> },
+    Failed(String),
+}
+
+/// Serves `nym://` URIs (mostly images embedded in pages) to egui.
+///
+/// The loader itself cannot talk to the mixnet. Unknown URIs are queued, the
+/// browser picks them up with [`NymAssetLoader::take_requests`] and hands
+/// the result back through [`NymAssetLoader::finish`].
+#[derive(Default)]
+pub struct NymAssetLoader {
+    assets: Mutex<HashMap<String, AssetState>>,
+    // URI and the server of the page that asked for it
+    requests: Mutex<Vec<(String, String)>>,
+    // Server of the page being drawn, relative URIs belong to it
+    page_server: Mutex<String>,
+}
+
+impl NymAssetLoader {
+    pub const ID: &'static str = egui::generate_loader_id!(NymAssetLoader);
+
+    /// Sets the server whose page is drawn next.
+    pub fn set_page_server(&self, server: &str) {
+        *self.page_server.lock().unwrap() = server.trim().to_string();
+    }
+
+    /// Queued URIs, each with the server of the page that asked for it.
+    pub fn take_requests(&self) -> Vec<(String, String)> {
+        std::mem::take(&mut *self.requests.lock().unwrap())
+    }
+
+    pub fn finish(&self, uri: &str, result: Result<(Vec<u8>, Option<String>), String>) {
+        let state = match result {
+            Ok((bytes, mime)) => AssetState::Ready { bytes: bytes.into(), mime },
+            Err(e) => AssetState::Failed(e),
+        };
+        self.assets.lock().unwrap().insert(uri.to_string(), state);
+    }
+}
+
+impl BytesLoader for NymAssetLoader {
+    fn id(&self) -> &str {
+        Self::ID
+    }
+
+    fn load(&self, _ctx: &Context, uri: &str) -> BytesLoadResult {
+        if !uri.starts_with("nym://") {
+            return Err(LoadError::NotSupported);
+        }
+
+        let mut assets = self.assets.lock().unwrap();
+        match assets.get(uri) {
+            Some(AssetState::Ready { bytes, mime }) => Ok(BytesPoll::Ready {
+                size: None,
+                bytes: Bytes::Shared(bytes.clone()),
+                mime: mime.clone(),
+            }),
+            Some(AssetState::Failed(e)) => Err(LoadError::Loading(e.clone())),
+            Some(AssetState::Pending) => Ok(BytesPoll::Pending { size: None }),
+            None => {
+                assets.insert(uri.to_string(), AssetState::Pending);
+                let server = self.page_server.lock().unwrap().clone();
+                self.requests.lock().unwrap().push((uri.to_string(), server));
+                Ok(BytesPoll::Pending { size: None })
+            }
+        }
+    }
+
+    fn forget(&self, uri: &str) {
+        self.assets.lock().unwrap().remove(uri);
+    }
+
+    fn forget_all(&self) {
+        self.assets.lock().unwrap().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.assets
+            .lock()
+            .unwrap()
+            .values()
+            .map(|state| match state {
+                AssetState::Ready { bytes, .. } => bytes.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    fn has_pending(&self) -> bool {
+        self.assets
+            .lock()
+            .unwrap()
+            .values()
+            .any(|state| matches!(state, AssetState::Pending))
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+// Binary files (images, PDFs, archives, ...) hosted next to the Markdown pages
+#[derive(Debug, Clone)]
+pub struct Asset {
+    pub bytes: Vec<u8>,
+    pub mime: &'static str,
+}
+
+#[derive(Debug, Default)]
+pub struct AssetStore {
+    assets: HashMap<String, Asset>,
+}
+
+impl AssetStore {
+    pub fn insert(&mut self, name: String, asset: Asset) {
+        self.assets.insert(name, asset);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Asset> {
+        self.assets.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Asset> {
+        self.assets.remove(name)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.assets.retain(|name, _| keep(name));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.assets.keys().map(|k| k.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+}
+
+pub fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "tar" => "application/x-tar",
+        "gz" | "tgz" => "application/gzip",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "css" => "text/css",
+        _ => "application/octet-stream",
+    }
+}
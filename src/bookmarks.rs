@@ -0,0 +1,461 @@
+// Bookmarks, kept in `bookmarks.json` in the browser data directory.
+//
+// Each bookmark sits in a folder (a `/` separated path, empty for the top
+// level) and may carry tags. With a passphrase set the list is stored sealed
+// with XChaCha20-Poly1305, under a key derived from the passphrase with
+// Argon2id, so the file no longer shows which sites were saved. Exports are
+// always plain JSON in the same format.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use crate::browser_data;
+
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+const FORMAT_VERSION: u32 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub title: String,
+    /// Full `nym://` address of the page
+    pub url: String,
+    #[serde(default)]
+    pub folder: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Unix time the bookmark was added
+    #[serde(default)]
+    pub added: u64,
+}
+
+impl Bookmark {
+    pub fn new(title: &str, url: &str) -> Self {
+        Self {
+            title: title.trim().to_string(),
+            url: url.trim().to_string(),
+            folder: String::new(),
+            tags: Vec::new(),
+            added: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    /// Whether the title, address, folder or a tag contains `query`, ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        query.is_empty()
+            || self.title.to_lowercase().contains(&query)
+            || self.url.to_lowercase().contains(&query)
+            || self.folder.to_lowercase().contains(&query)
+            || self.tags.iter().any(|tag| tag.to_lowercase().contains(&query))
+    }
+}
+
+/// Splits comma separated tags as typed in the bookmark editor.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|known| known == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Cleans up a folder path: no empty parts or surrounding slashes.
+pub fn normalize_folder(folder: &str) -> String {
+    folder
+        .split('/')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Layout of the bookmarks file and of exports
+#[derive(Debug, Serialize, Deserialize)]
+struct BookmarkFile {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    bookmarks: Vec<Bookmark>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed: Option<Sealed>,
+}
+
+// The bookmark list encrypted under a passphrase, all fields in hex
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sealed {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Clone)]
+struct StoreKey {
+    key: [u8; KEY_LEN],
+    salt: [u8; SALT_LEN],
+}
+
+impl StoreKey {
+    fn derive(passphrase: &str, salt: [u8; SALT_LEN]) -> Result<Self, String> {
+        let mut key = [0u8; KEY_LEN];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| format!("Could not derive a key: {}", e))?;
+        Ok(Self { key, salt })
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new_from_slice(&self.key).expect("key has the cipher's length")
+    }
+
+    fn seal(&self, bookmarks: &[Bookmark]) -> Result<Sealed, String> {
+        let plaintext = serde_json::to_string(bookmarks).map_err(|e| e.to_string())?;
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = self
+            .cipher()
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| "Encryption failed".to_string())?;
+        Ok(Sealed {
+            salt: hex::encode(self.salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    fn open(&self, sealed: &Sealed) -> Result<Vec<Bookmark>, String> {
+        let nonce = hex::decode(&sealed.nonce).ok().filter(|nonce| nonce.len() == NONCE_LEN);
+        let ciphertext = hex::decode(&sealed.ciphertext).ok();
+        let (Some(nonce), Some(ciphertext)) = (nonce, ciphertext) else {
+            return Err("The bookmarks file is damaged".to_string());
+        };
+        let plaintext = self
+            .cipher()
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| "Wrong passphrase".to_string())?;
+        let plaintext = String::from_utf8(plaintext).map_err(|e| e.to_string())?;
+        serde_json::from_str(&plaintext).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct BookmarkStore {
+    path: Option<PathBuf>,
+    bookmarks: Vec<Bookmark>,
+    // Set while the list is stored encrypted and has been unlocked
+    key: Option<StoreKey>,
+    // Encrypted list still waiting for its passphrase
+    locked: Option<Sealed>,
+}
+
+impl BookmarkStore {
+    /// Loads the bookmarks from the browser data directory. Without a usable
+    /// directory bookmarks are only kept for this session.
+    pub fn load() -> Self {
+        match browser_data::ensure_data_dir() {
+            Ok(dir) => Self::load_from(dir.join(BOOKMARKS_FILE)),
+            Err(e) => {
+                eprintln!("Bookmarks will not be saved: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        let mut store = Self { path: Some(path.clone()), ..Self::default() };
+        match fs::read_to_string(&path) {
+            Ok(text) => match serde_json::from_str::<BookmarkFile>(&text) {
+                Ok(file) => {
+                    store.bookmarks = file.bookmarks;
+                    store.locked = file.sealed;
+                }
+                Err(e) => {
+                    // Keep the damaged file out of the way instead of overwriting it
+                    eprintln!("Error reading {}: {}", path.display(), e);
+                    store.path = None;
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                eprintln!("Error reading {}: {}", path.display(), e);
+                store.path = None;
+            }
+        }
+        store
+    }
+
+    /// Whether the bookmarks are encrypted and not unlocked yet.
+    pub fn is_locked(&self) -> bool {
+        self.locked.is_some()
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.key.is_some() || self.locked.is_some()
+    }
+
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), String> {
+        let Some(sealed) = &self.locked else { return Ok(()) };
+        let salt = hex::decode(&sealed.salt)
+            .ok()
+            .and_then(|salt| <[u8; SALT_LEN]>::try_from(salt).ok())
+            .ok_or_else(|| "The bookmarks file is damaged".to_string())?;
+        let key = StoreKey::derive(passphrase, salt)?;
+        self.bookmarks = key.open(sealed)?;
+        self.key = Some(key);
+        self.locked = None;
+        Ok(())
+    }
+
+    /// Encrypts the stored list with `passphrase`, or stores it in the clear again with `None`.
+    pub fn set_passphrase(&mut self, passphrase: Option<&str>) -> Result<(), String> {
+        self.check_unlocked()?;
+        self.key = match passphrase {
+            Some("") => return Err("The passphrase is empty".to_string()),
+            Some(passphrase) => Some(StoreKey::derive(passphrase, rand::random())?),
+            None => None,
+        };
+        self.save()
+    }
+
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    pub fn find(&self, url: &str) -> Option<usize> {
+        self.bookmarks.iter().position(|bookmark| bookmark.url == url.trim())
+    }
+
+    /// Adds a bookmark, replacing any other one for the same address.
+    pub fn add(&mut self, bookmark: Bookmark) -> Result<(), String> {
+        self.check_unlocked()?;
+        match self.find(&bookmark.url) {
+            Some(index) => self.bookmarks[index] = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+        self.save()
+    }
+
+    pub fn update(&mut self, index: usize, bookmark: Bookmark) -> Result<(), String> {
+        self.check_unlocked()?;
+        let Some(slot) = self.bookmarks.get_mut(index) else { return Ok(()) };
+        *slot = bookmark;
+        self.save()
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<(), String> {
+        self.check_unlocked()?;
+        if index < self.bookmarks.len() {
+            self.bookmarks.remove(index);
+        }
+        self.save()
+    }
+
+    /// Every folder in use, parents included, sorted.
+    pub fn folders(&self) -> Vec<String> {
+        let mut folders: Vec<String> = Vec::new();
+        for bookmark in &self.bookmarks {
+            let parts: Vec<&str> = bookmark.folder.split('/').filter(|part| !part.is_empty()).collect();
+            for depth in 1..=parts.len() {
+                folders.push(parts[..depth].join("/"));
+            }
+        }
+        folders.sort();
+        folders.dedup();
+        folders
+    }
+
+    /// Writes all bookmarks to `path` as plain JSON.
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        self.check_unlocked()?;
+        let file = BookmarkFile {
+            version: FORMAT_VERSION,
+            bookmarks: self.bookmarks.clone(),
+            sealed: None,
+        };
+        let text = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+    }
+
+    /// Adds the bookmarks exported to `path` that are not here yet,
+    /// returning how many were added.
+    pub fn import(&mut self, path: &Path) -> Result<usize, String> {
+        self.check_unlocked()?;
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let file: BookmarkFile = serde_json::from_str(&text)
+            .map_err(|e| format!("{} is not a bookmark export: {}", path.display(), e))?;
+        if file.sealed.is_some() {
+            return Err("Encrypted bookmark files cannot be imported, export them first".to_string());
+        }
+
+        let mut added = 0;
+        for mut bookmark in file.bookmarks {
+            if bookmark.url.trim().is_empty() || self.find(&bookmark.url).is_some() {
+                continue;
+            }
+            bookmark.folder = normalize_folder(&bookmark.folder);
+            self.bookmarks.push(bookmark);
+            added += 1;
+        }
+        self.save()?;
+        Ok(added)
+    }
+
+    // Changes made while locked would overwrite the encrypted list
+    fn check_unlocked(&self) -> Result<(), String> {
+        if self.is_locked() {
+            return Err("Unlock the bookmarks first".to_string());
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else { return Ok(()) };
+
+        let file = match &self.key {
+            Some(key) => BookmarkFile { version: FORMAT_VERSION, bookmarks: Vec::new(), sealed: Some(key.seal(&self.bookmarks)?) },
+            None => BookmarkFile { version: FORMAT_VERSION, bookmarks: self.bookmarks.clone(), sealed: None },
+        };
+        let text = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        browser_data::write_private(path, &text).map_err(|e| format!("Error saving {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const URL: &str = "nym://abc.def@gateway/blog/post1";
+
+    fn store_in(dir: &TempDir) -> BookmarkStore {
+        BookmarkStore::load_from(dir.path().join(BOOKMARKS_FILE))
+    }
+
+    fn sealed_store(dir: &TempDir) -> BookmarkStore {
+        let mut store = store_in(dir);
+        store.add(Bookmark::new("Post", URL)).unwrap();
+        store.set_passphrase(Some("correct horse")).unwrap();
+        store_in(dir)
+    }
+
+    #[test]
+    fn sealed_bookmarks_open_with_their_passphrase() {
+        let dir = TempDir::new().unwrap();
+        let mut store = sealed_store(&dir);
+        let text = fs::read_to_string(dir.path().join(BOOKMARKS_FILE)).unwrap();
+        assert!(!text.contains("abc.def@gateway"), "{}", text);
+        assert!(store.is_locked() && store.is_encrypted());
+        assert!(store.bookmarks().is_empty());
+
+        store.unlock("correct horse").unwrap();
+        assert!(!store.is_locked() && store.is_encrypted());
+        assert_eq!(store.bookmarks().len(), 1);
+        assert_eq!(store.bookmarks()[0].url, URL);
+
+        // Changes stay sealed under the same passphrase
+        store.add(Bookmark::new("About", "nym://abc.def@gateway/about")).unwrap();
+        let mut reloaded = store_in(&dir);
+        reloaded.unlock("correct horse").unwrap();
+        assert_eq!(reloaded.bookmarks(), store.bookmarks());
+    }
+
+    #[test]
+    fn a_wrong_passphrase_keeps_them_locked() {
+        let dir = TempDir::new().unwrap();
+        let mut store = sealed_store(&dir);
+        assert_eq!(store.unlock("wrong horse"), Err("Wrong passphrase".to_string()));
+        assert!(store.is_locked());
+        assert!(store.bookmarks().is_empty());
+    }
+
+    #[test]
+    fn nothing_changes_while_locked() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join(BOOKMARKS_FILE);
+        let export = dir.path().join("export.json");
+        let mut store = sealed_store(&dir);
+        let sealed = fs::read_to_string(&file).unwrap();
+
+        let locked = Err("Unlock the bookmarks first".to_string());
+        assert_eq!(store.add(Bookmark::new("About", "nym://abc.def@gateway/about")), locked);
+        assert_eq!(store.set_passphrase(None), locked);
+        assert_eq!(store.export(&export), locked);
+        assert_eq!(store.import(&export).map(|_| ()), locked);
+        assert!(!export.exists());
+        assert_eq!(fs::read_to_string(&file).unwrap(), sealed);
+    }
+
+    #[test]
+    fn imports_add_only_new_addresses() {
+        let dir = TempDir::new().unwrap();
+        let export = dir.path().join("export.json");
+        let mut other = BookmarkStore::default();
+        let mut nested = Bookmark::new("About", "nym://abc.def@gateway/about");
+        nested.folder = "/sites//blog/".to_string();
+        other.add(Bookmark::new("Renamed post", URL)).unwrap();
+        other.add(nested).unwrap();
+        other.export(&export).unwrap();
+
+        let mut store = store_in(&dir);
+        store.add(Bookmark::new("Post", URL)).unwrap();
+        assert_eq!(store.import(&export), Ok(1));
+        assert_eq!(store.bookmarks()[0].title, "Post");
+        assert_eq!(store.bookmarks()[1].folder, "sites/blog");
+        assert_eq!(store.import(&export), Ok(0));
+        assert_eq!(store_in(&dir).bookmarks(), store.bookmarks());
+    }
+
+    #[test]
+    fn sealed_files_cannot_be_imported() {
+        let dir = TempDir::new().unwrap();
+        sealed_store(&dir);
+        let mut store = BookmarkStore::default();
+        let result = store.import(&dir.path().join(BOOKMARKS_FILE));
+        assert_eq!(result, Err("Encrypted bookmark files cannot be imported, export them first".to_string()));
+        assert!(store.bookmarks().is_empty());
+    }
+
+    #[test]
+    fn removing_the_passphrase_stores_plain_json_again() {
+        let dir = TempDir::new().unwrap();
+        let mut store = sealed_store(&dir);
+        store.unlock("correct horse").unwrap();
+        store.set_passphrase(None).unwrap();
+        assert!(!store.is_encrypted());
+
+        let text = fs::read_to_string(dir.path().join(BOOKMARKS_FILE)).unwrap();
+        assert!(text.contains(URL), "{}", text);
+        let reloaded = store_in(&dir);
+        assert!(!reloaded.is_encrypted());
+        assert_eq!(reloaded.bookmarks(), store.bookmarks());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_file_is_only_readable_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join(BOOKMARKS_FILE);
+        fs::write(&file, "").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut store = BookmarkStore { path: Some(file.clone()), ..BookmarkStore::default() };
+        store.add(Bookmark::new("Post", URL)).unwrap();
+        assert_eq!(fs::metadata(&file).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
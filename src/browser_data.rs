@@ -0,0 +1,43 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Directory for everything the browser keeps between runs
+pub fn get_data_dir() -> PathBuf {
+    let mut data_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    data_dir.push("NymView");
+    data_dir.push("browser");
+    data_dir
+}
+
+pub fn ensure_data_dir() -> std::io::Result<PathBuf> {
+    let data_dir = get_data_dir();
+    std::fs::create_dir_all(&data_dir)?;
+    Ok(data_dir)
+}
+
+/// Replaces the file at `path` with `text`, readable by the owner only.
+///
+/// The text goes to a temporary file next to it first, so a crash while
+/// saving never leaves a truncated file behind.
+pub fn write_private(path: &Path, text: &str) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    match fs::remove_file(&temp) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
@@ -0,0 +1,200 @@
+// Splitting of large responses into sequenced chunks and their reassembly.
+//
+// Every chunk is a complete response envelope carrying the original request
+// id plus the chunk index, chunk count and total body length as headers.
+// The browser asks for missing chunks again by repeating the request with a
+// `resend-chunks` header listing the indices it still needs.
+
+use std::fmt;
+use std::time::Instant;
+
+use crate::protocol::{Response, Status};
+
+pub const CHUNK_SIZE: usize = 32 * 1024;
+pub const MAX_TRANSFER_SIZE: usize = 16 * 1024 * 1024;
+
+pub const HEADER_CHUNK_INDEX: &str = "chunk-index";
+pub const HEADER_CHUNK_COUNT: &str = "chunk-count";
+pub const HEADER_TOTAL_LENGTH: &str = "total-length";
+pub const HEADER_RESEND_CHUNKS: &str = "resend-chunks";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkError {
+    MissingHeader(&'static str),
+    InvalidHeader(&'static str),
+    TooLarge(usize),
+    Inconsistent,
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkError::MissingHeader(name) => write!(f, "chunk header '{}' is missing", name),
+            ChunkError::InvalidHeader(name) => write!(f, "chunk header '{}' is invalid", name),
+            ChunkError::TooLarge(size) => write!(f, "transfer of {} bytes exceeds the size limit", size),
+            ChunkError::Inconsistent => write!(f, "chunk does not belong to this transfer"),
+        }
+    }
+}
+
+impl std::error::Error for ChunkError {}
+
+/// Splits a response into chunks, keeping only the indices in `only` if given.
+// Only the server splits responses and reads resend requests
+#[allow(dead_code)]
+pub fn split_response(response: &Response, only: Option<&[u32]>) -> Vec<Response> {
+    let pieces: Vec<&[u8]> = if response.body.is_empty() {
+        vec![&[][..]]
+    } else {
+        response.body.chunks(CHUNK_SIZE).collect()
+    };
+    let count = pieces.len();
+
+    pieces
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| only.is_none_or(|wanted| wanted.contains(&(*index as u32))))
+        .map(|(index, piece)| {
+            let mut chunk = Response::with_status(response.id, response.status, piece);
+            chunk.headers = response.headers.clone();
+            chunk.headers.push((HEADER_CHUNK_INDEX.to_string(), index.to_string()));
+            chunk.headers.push((HEADER_CHUNK_COUNT.to_string(), count.to_string()));
+            chunk.headers.push((HEADER_TOTAL_LENGTH.to_string(), response.body.len().to_string()));
+            chunk
+        })
+        .collect()
+}
+
+/// Parses the value of a `resend-chunks` header.
+#[allow(dead_code)]
+pub fn parse_resend_list(value: &str) -> Vec<u32> {
+    value
+        .split(',')
+        .filter_map(|index| index.trim().parse().ok())
+        .collect()
+}
+
+// Only the browser asks for missing chunks
+#[allow(dead_code)]
+pub fn format_resend_list(indices: &[u32]) -> String {
+    indices
+        .iter()
+        .map(|index| index.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn chunk_header(response: &Response, name: &'static str) -> Result<usize, ChunkError> {
+    response
+        .header(name)
+        .ok_or(ChunkError::MissingHeader(name))?
+        .parse()
+        .map_err(|_| ChunkError::InvalidHeader(name))
+}
+
+/// Collects the chunks of one response until all of them have arrived.
+#[derive(Debug, Clone)]
+pub struct ChunkAssembler {
+    status: Status,
+    headers: Vec<(String, String)>,
+    total_length: usize,
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+    pub last_activity: Instant,
+}
+
+impl ChunkAssembler {
+    pub fn new(first: &Response) -> Result<Self, ChunkError> {
+        let count = chunk_header(first, HEADER_CHUNK_COUNT)?;
+        let total_length = chunk_header(first, HEADER_TOTAL_LENGTH)?;
+        if total_length > MAX_TRANSFER_SIZE {
+            return Err(ChunkError::TooLarge(total_length));
+        }
+        if count != total_length.div_ceil(CHUNK_SIZE).max(1) {
+            return Err(ChunkError::InvalidHeader(HEADER_CHUNK_COUNT));
+        }
+
+        let headers = first
+            .headers
+            .iter()
+            .filter(|(key, _)| {
+                ![HEADER_CHUNK_INDEX, HEADER_CHUNK_COUNT, HEADER_TOTAL_LENGTH]
+                    .iter()
+                    .any(|name| key.eq_ignore_ascii_case(name))
+            })
+            .cloned()
+            .collect();
+
+        Ok(Self {
+            status: first.status,
+            headers,
+            total_length,
+            chunks: vec![None; count],
+            received: 0,
+            last_activity: Instant::now(),
+        })
+    }
+
+    pub fn insert(&mut self, chunk: Response) -> Result<(), ChunkError> {
+        let index = chunk_header(&chunk, HEADER_CHUNK_INDEX)?;
+        if chunk.status != self.status
+            || chunk_header(&chunk, HEADER_CHUNK_COUNT)? != self.chunks.len()
+            || chunk_header(&chunk, HEADER_TOTAL_LENGTH)? != self.total_length
+            || index >= self.chunks.len()
+        {
+            return Err(ChunkError::Inconsistent);
+        }
+
+        self.last_activity = Instant::now();
+        if self.chunks[index].is_none() {
+            self.chunks[index] = Some(chunk.body);
+            self.received += 1;
+        }
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.received == self.chunks.len()
+    }
+
+    // Progress of a transfer, shown by the browser
+    #[allow(dead_code)]
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    #[allow(dead_code)]
+    pub fn count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn progress(&self) -> f32 {
+        self.received as f32 / self.chunks.len() as f32
+    }
+
+    #[allow(dead_code)]
+    pub fn missing(&self) -> Vec<u32> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.is_none())
+            .map(|(index, _)| index as u32)
+            .collect()
+    }
+
+    /// Joins all chunks back into the original response.
+    pub fn finish(self, id: u64) -> Result<Response, ChunkError> {
+        let mut body = Vec::with_capacity(self.total_length);
+        for chunk in self.chunks {
+            body.extend_from_slice(&chunk.ok_or(ChunkError::Inconsistent)?);
+        }
+        if body.len() != self.total_length {
+            return Err(ChunkError::Inconsistent);
+        }
+
+        let mut response = Response::with_status(id, self.status, body);
+        response.headers = self.headers;
+        Ok(response)
+    }
+}
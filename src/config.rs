@@ -1,15 +1,349 @@
-use std::path::PathBuf;
+// Server settings from a TOML file with named profiles.
+//
+// Top level settings apply to every profile, and each `[profiles.<name>]`
+// table only lists what differs for that site:
+//
+//   workers = 4
+//
+//   [rate_limit]
+//   per_sender = 1.0
+//
+//   [profiles.blog]
+//   pages_dir = "/srv/blog"
+//   gateway = ["<gateway identity key>", "<another one>"]
+//
+// Command line flags override both. Relative paths in the file are relative
+// to the file itself.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::admission::AdmissionConfig;
+use crate::gateway::GatewayChoice;
+use crate::rate_limit::{self, BucketConfig, RateLimitConfig};
+use crate::worker_pool::PoolConfig;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+fn nymview_dir() -> PathBuf {
+    let mut dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+    dir.push("NymView");
+    dir
+}
 
 pub fn get_config_dir() -> PathBuf {
-    let mut config_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
-    config_dir.push("NymView");
-    config_dir.push("mixnet_server");
-    config_dir
+    nymview_dir().join("mixnet_server")
+}
+
+pub fn default_config_file() -> PathBuf {
+    nymview_dir().join("server.toml")
+}
+
+// Each profile is its own site, so it gets its own Nym identity and keys
+fn default_identity_dir(profile: &str) -> PathBuf {
+    if profile == DEFAULT_PROFILE {
+        get_config_dir()
+    } else {
+        nymview_dir().join("profiles").join(profile)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub pages_dir: Option<PathBuf>,
+    /// Where the Nym client, site key and admin keys are kept
+    pub identity_dir: Option<PathBuf>,
+    pub gateway: Option<GatewaySetting>,
+    pub admin_keys: Option<PathBuf>,
+    pub workers: Option<usize>,
+    pub queue_size: Option<usize>,
+    #[serde(default)]
+    pub rate_limit: RateLimitSettings,
+    #[serde(default)]
+    pub pow: PowSettings,
+    #[serde(default)]
+    pub logging: LoggingSettings,
+}
+
+/// One gateway identity key, or a list to pick from at random.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum GatewaySetting {
+    One(String),
+    AnyOf(Vec<String>),
+}
+
+impl GatewaySetting {
+    fn choice(self) -> GatewayChoice {
+        match self {
+            GatewaySetting::One(key) => GatewayChoice::from_keys(vec![key]),
+            GatewaySetting::AnyOf(keys) => GatewayChoice::from_keys(keys),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitSettings {
+    pub per_sender: Option<f64>,
+    pub per_sender_burst: Option<f64>,
+    pub global: Option<f64>,
+    pub global_burst: Option<f64>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PowSettings {
+    pub difficulty: Option<u8>,
+    pub queue_threshold: Option<usize>,
+    /// Seconds
+    pub cool_down: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingSettings {
+    /// Print a line for every request served
+    pub requests: Option<bool>,
+    /// Seconds between worker statistics in the log, 0 turns them off
+    pub metrics_interval: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogConfig {
+    pub requests: bool,
+    pub metrics_interval: Option<Duration>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            requests: false,
+            metrics_interval: Some(Duration::from_secs(60)),
+        }
+    }
 }
 
-pub fn ensure_config_dir() -> std::io::Result<PathBuf> {
-    let config_dir = get_config_dir();
-    std::fs::create_dir_all(&config_dir)?;
-    println!("Persistence directory: {:?}", config_dir);
-    Ok(config_dir)
+/// Everything a server needs to know about the site it hosts.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub profile: String,
+    pub pages_dir: PathBuf,
+    pub identity_dir: PathBuf,
+    pub gateway: GatewayChoice,
+    pub admin_keys: PathBuf,
+    pub pool: PoolConfig,
+    pub rate_limits: RateLimitConfig,
+    pub admission: AdmissionConfig,
+    pub logging: LogConfig,
+}
+
+impl Settings {
+    /// Settings of `profile` from the config file at `path`, or at the
+    /// default location when `path` is `None`. A missing default file just
+    /// means no settings.
+    pub fn load(path: Option<&Path>, profile: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = path.map_or_else(default_config_file, Path::to_path_buf);
+        let contents = match fs::read_to_string(&file) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && path.is_none() => {
+                return match profile {
+                    DEFAULT_PROFILE => Ok(Self::default()),
+                    _ => Err(format!("No profile '{}', {} does not exist", profile, file.display()).into()),
+                };
+            }
+            Err(e) => return Err(format!("Cannot read {}: {}", file.display(), e).into()),
+        };
+
+        let settings = Self::parse(&contents, profile)
+            .map_err(|e| format!("Invalid config {}: {}", file.display(), e))?;
+        let base_dir = file.parent().unwrap_or(Path::new("."));
+        Ok(settings.relative_to(base_dir))
+    }
+
+    /// Names of all profiles in the config file at `path` (or the default location).
+    pub fn profile_names(path: Option<&Path>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let file = path.map_or_else(default_config_file, Path::to_path_buf);
+        let contents = fs::read_to_string(&file).map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e| format!("Invalid config {}: {}", file.display(), e))?;
+        Ok(table
+            .get("profiles")
+            .and_then(toml::Value::as_table)
+            .map(|profiles| profiles.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn parse(contents: &str, profile: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut table: toml::Table = contents.parse()?;
+        let profiles: BTreeMap<String, Settings> = match table.remove("profiles") {
+            Some(profiles) => profiles.try_into()?,
+            None => BTreeMap::new(),
+        };
+        let base: Settings = toml::Value::Table(table).try_into()?;
+
+        match profiles.get(profile) {
+            Some(selected) => Ok(base.merge(selected.clone())),
+            None if profile == DEFAULT_PROFILE => Ok(base),
+            None => {
+                let available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+                Err(format!("no profile '{}' (available: {})", profile, available.join(", ")).into())
+            }
+        }
+    }
+
+    fn relative_to(mut self, base_dir: &Path) -> Self {
+        for path in [&mut self.pages_dir, &mut self.identity_dir, &mut self.admin_keys] {
+            if let Some(path) = path.as_mut() {
+                *path = base_dir.join(&*path);
+            }
+        }
+        self
+    }
+
+    /// These settings with everything set in `over` replacing them.
+    pub fn merge(self, over: Settings) -> Self {
+        Self {
+            pages_dir: over.pages_dir.or(self.pages_dir),
+            identity_dir: over.identity_dir.or(self.identity_dir),
+            gateway: over.gateway.or(self.gateway),
+            admin_keys: over.admin_keys.or(self.admin_keys),
+            workers: over.workers.or(self.workers),
+            queue_size: over.queue_size.or(self.queue_size),
+            rate_limit: RateLimitSettings {
+                per_sender: over.rate_limit.per_sender.or(self.rate_limit.per_sender),
+                per_sender_burst: over.rate_limit.per_sender_burst.or(self.rate_limit.per_sender_burst),
+                global: over.rate_limit.global.or(self.rate_limit.global),
+                global_burst: over.rate_limit.global_burst.or(self.rate_limit.global_burst),
+                message: over.rate_limit.message.or(self.rate_limit.message),
+            },
+            pow: PowSettings {
+                difficulty: over.pow.difficulty.or(self.pow.difficulty),
+                queue_threshold: over.pow.queue_threshold.or(self.pow.queue_threshold),
+                cool_down: over.pow.cool_down.or(self.pow.cool_down),
+            },
+            logging: LoggingSettings {
+                requests: over.logging.requests.or(self.logging.requests),
+                metrics_interval: over.logging.metrics_interval.or(self.logging.metrics_interval),
+            },
+        }
+    }
+
+    /// Fills in defaults for everything left unset.
+    pub fn resolve(self, profile: &str) -> ServerConfig {
+        let identity_dir = self.identity_dir.unwrap_or_else(|| default_identity_dir(profile));
+        let pool = PoolConfig::default();
+        let admission = AdmissionConfig::default();
+        let logging = LogConfig::default();
+
+        // A rate of 0 turns the limit off
+        let bucket = |rate: Option<f64>, burst: Option<f64>, default: BucketConfig| {
+            BucketConfig::new(rate.unwrap_or(default.rate), burst.unwrap_or(default.burst))
+        };
+        let limits = &self.rate_limit;
+
+        ServerConfig {
+            profile: profile.to_string(),
+            pages_dir: self.pages_dir.unwrap_or_else(|| PathBuf::from("./pages")),
+            admin_keys: self.admin_keys.unwrap_or_else(|| identity_dir.join("admin_keys")),
+            identity_dir,
+            gateway: self.gateway.map_or(GatewayChoice::Default, GatewaySetting::choice),
+            pool: PoolConfig {
+                workers: self.workers.unwrap_or(pool.workers),
+                queue_size: self.queue_size.unwrap_or(pool.queue_size),
+            },
+            rate_limits: RateLimitConfig {
+                per_sender: bucket(limits.per_sender, limits.per_sender_burst, rate_limit::DEFAULT_PER_SENDER),
+                global: bucket(limits.global, limits.global_burst, rate_limit::DEFAULT_GLOBAL),
+                message: limits.message.clone().unwrap_or_else(|| RateLimitConfig::default().message),
+            },
+            admission: AdmissionConfig {
+                difficulty: self.pow.difficulty.unwrap_or(admission.difficulty),
+                queue_threshold: self.pow.queue_threshold.unwrap_or(admission.queue_threshold),
+                cool_down: self.pow.cool_down.map_or(admission.cool_down, Duration::from_secs),
+            },
+            logging: LogConfig {
+                requests: self.logging.requests.unwrap_or(logging.requests),
+                metrics_interval: match self.logging.metrics_interval {
+                    Some(0) => None,
+                    Some(seconds) => Some(Duration::from_secs(seconds)),
+                    None => logging.metrics_interval,
+                },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const CONFIG: &str = r#"
+workers = 2
+
+[rate_limit]
+message = "Shared by all sites"
+
+[profiles.blog]
+pages_dir = "blog"
+identity_dir = "blog-identity"
+
+[profiles.docs]
+pages_dir = "docs"
+workers = 4
+"#;
+
+    #[test]
+    fn profiles_inherit_the_top_level_and_flags_override_both() {
+        let dir = TempDir::new().unwrap();
+        let config_file = dir.path().join("server.toml");
+        fs::write(&config_file, CONFIG).unwrap();
+
+        let docs = Settings::load(Some(&config_file), "docs").unwrap().resolve("docs");
+        assert_eq!(docs.pool.workers, 4);
+        assert_eq!(docs.pages_dir, dir.path().join("docs"));
+        assert_eq!(docs.rate_limits.message, "Shared by all sites");
+        let overridden = Settings::load(Some(&config_file), "docs")
+            .unwrap()
+            .merge(Settings { workers: Some(6), ..Settings::default() })
+            .resolve("docs");
+        assert_eq!(overridden.pool.workers, 6);
+
+        let blog = Settings::load(Some(&config_file), "blog").unwrap().resolve("blog");
+        assert_eq!(blog.pool.workers, 2);
+        assert_eq!(blog.pages_dir, dir.path().join("blog"));
+        assert_eq!(blog.admin_keys, dir.path().join("blog-identity/admin_keys"));
+        assert_eq!(Settings::profile_names(Some(&config_file)).unwrap(), ["blog", "docs"]);
+    }
+
+    #[test]
+    fn unknown_profiles_and_keys_are_errors() {
+        let dir = TempDir::new().unwrap();
+        let config_file = dir.path().join("server.toml");
+        fs::write(&config_file, CONFIG).unwrap();
+
+        let error = Settings::load(Some(&config_file), "shop").unwrap_err().to_string();
+        assert!(error.contains("available: blog, docs"), "{}", error);
+        fs::write(dir.path().join("typo.toml"), "wokers = 2\n").unwrap();
+        assert!(Settings::load(Some(&dir.path().join("typo.toml")), DEFAULT_PROFILE).is_err());
+    }
+
+    #[test]
+    fn a_gateway_list_is_picked_from_at_random() {
+        const FIRST: &str = "2xU4CBE6QiiYt6EyBXSALwxkNvM7gqJfjHXaMkjiFmYW";
+        const SECOND: &str = "E3mvZTHQCdBvhfr178Swx9g4QG3kkRUun7YnToLMcMbM";
+
+        let dir = TempDir::new().unwrap();
+        let config_file = dir.path().join("server.toml");
+        fs::write(&config_file, format!("gateway = [\"{}\", \"{}\"]\n", FIRST, SECOND)).unwrap();
+        let config = Settings::load(Some(&config_file), DEFAULT_PROFILE).unwrap().resolve(DEFAULT_PROFILE);
+        assert_eq!(config.gateway, GatewayChoice::RandomFrom(vec![FIRST.to_string(), SECOND.to_string()]));
+    }
 }
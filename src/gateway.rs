@@ -0,0 +1,185 @@
+// Choice of the entry gateway a mixnet client connects through.
+//
+// Gateways are named by their identity key. Choices are stored as a plain
+// list of keys, one per line: none means the SDK's default, one a specific
+// gateway, several a random pick among them.
+
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum GatewayChoice {
+    /// Whatever the SDK picks
+    #[default]
+    Default,
+    Specific(String),
+    /// A random one of these, e.g. gateways run by people you trust
+    RandomFrom(Vec<String>),
+}
+
+impl GatewayChoice {
+    /// Builds a choice from a list of keys, ignoring blanks and repeats.
+    pub fn from_keys(keys: Vec<String>) -> Self {
+        let mut seen = HashSet::new();
+        let mut keys: Vec<String> = keys
+            .into_iter()
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty() && seen.insert(key.clone()))
+            .collect();
+        match keys.len() {
+            0 => GatewayChoice::Default,
+            1 => GatewayChoice::Specific(keys.remove(0)),
+            _ => GatewayChoice::RandomFrom(keys),
+        }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        match self {
+            GatewayChoice::Default => &[],
+            GatewayChoice::Specific(key) => std::slice::from_ref(key),
+            GatewayChoice::RandomFrom(keys) => keys,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self.keys().iter().find(|key| !is_identity_key(key)) {
+            Some(key) => Err(format!("'{}' is not a gateway identity key", key)),
+            None => Ok(()),
+        }
+    }
+
+    /// The gateway to ask for, sticking with `remembered` where the choice allows it.
+    pub fn pick(&self, remembered: Option<&str>) -> Option<String> {
+        let remembered = remembered.map(str::to_string);
+        match self {
+            GatewayChoice::Default => remembered,
+            GatewayChoice::Specific(key) => Some(key.clone()),
+            GatewayChoice::RandomFrom(keys) => remembered
+                .filter(|remembered| keys.contains(remembered))
+                .or_else(|| keys.choose(&mut rand::thread_rng()).cloned()),
+        }
+    }
+
+    // Shown in the browser
+    #[allow(dead_code)]
+    pub fn describe(&self) -> String {
+        match self {
+            GatewayChoice::Default => "default gateway".to_string(),
+            GatewayChoice::Specific(key) => format!("gateway {}", key),
+            GatewayChoice::RandomFrom(keys) => format!("random gateway out of {}", keys.len()),
+        }
+    }
+}
+
+/// Whether `key` looks like a gateway identity key (32 bytes in base58).
+pub fn is_identity_key(key: &str) -> bool {
+    (32..=44).contains(&key.len()) && key.chars().all(|c| BASE58_ALPHABET.contains(c))
+}
+
+/// The gateway part of a Nym address (`client.encryption@gateway`).
+pub fn gateway_of(address: &str) -> Option<&str> {
+    address
+        .trim()
+        .trim_start_matches("nym://")
+        .rsplit_once('@')
+        .map(|(_, gateway)| gateway)
+        .filter(|gateway| !gateway.is_empty())
+}
+
+/// Shortens a key for display, e.g. in the status line.
+#[allow(dead_code)]
+pub fn short(key: &str) -> String {
+    match key.char_indices().nth(8) {
+        Some((end, _)) => format!("{}…", &key[..end]),
+        None => key.to_string(),
+    }
+}
+
+/// Reads a list of gateway keys; a missing file is an empty list.
+pub fn load(path: &Path) -> std::io::Result<Vec<String>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+pub fn save(path: &Path, keys: &[String]) -> std::io::Result<()> {
+    let mut contents = String::new();
+    for key in keys {
+        contents.push_str(key);
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST: &str = "2xU4CBE6QiiYt6EyBXSALwxkNvM7gqJfjHXaMkjiFmYW";
+    const SECOND: &str = "E3mvZTHQCdBvhfr178Swx9g4QG3kkRUun7YnToLMcMbM";
+
+    #[test]
+    fn only_identity_keys_are_valid() {
+        assert!(GatewayChoice::Specific(FIRST.to_string()).validate().is_ok());
+        assert!(GatewayChoice::Specific("not a key".to_string()).validate().is_err());
+        assert!(GatewayChoice::RandomFrom(vec![FIRST.to_string(), "0OIl".to_string()]).validate().is_err());
+    }
+
+    #[test]
+    fn picks_stick_with_the_remembered_gateway_where_allowed() {
+        // Random picks stay with it while it is a candidate, a specific choice moves away
+        let random = GatewayChoice::RandomFrom(vec![FIRST.to_string(), SECOND.to_string()]);
+        assert_eq!(random.pick(Some(SECOND)).as_deref(), Some(SECOND));
+        let pick = random.pick(Some("retired")).unwrap();
+        assert!(pick == FIRST || pick == SECOND);
+        assert_eq!(GatewayChoice::Specific(FIRST.to_string()).pick(Some(SECOND)).as_deref(), Some(FIRST));
+        assert_eq!(GatewayChoice::Default.pick(Some(SECOND)).as_deref(), Some(SECOND));
+        assert_eq!(GatewayChoice::Default.pick(None), None);
+    }
+
+    #[test]
+    fn repeated_keys_count_once() {
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+        assert_eq!(GatewayChoice::from_keys(keys(&[FIRST, " ", FIRST])), GatewayChoice::Specific(FIRST.to_string()));
+        assert_eq!(
+            GatewayChoice::from_keys(keys(&[FIRST, SECOND, FIRST, SECOND])),
+            GatewayChoice::RandomFrom(keys(&[FIRST, SECOND]))
+        );
+        assert_eq!(
+            GatewayChoice::from_keys(keys(&[SECOND, FIRST, &format!(" {} ", SECOND)])),
+            GatewayChoice::RandomFrom(keys(&[SECOND, FIRST]))
+        );
+        assert_eq!(GatewayChoice::from_keys(keys(&["", "  "])), GatewayChoice::Default);
+    }
+
+    #[test]
+    fn remembered_gateways_are_read_back() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("gateway");
+        assert_eq!(load(&path).unwrap(), Vec::<String>::new());
+
+        save(&path, &[SECOND.to_string()]).unwrap();
+        assert_eq!(load(&path).unwrap(), [SECOND]);
+        fs::write(&path, format!("# picked last time\n\n{}  # still fine\n", FIRST)).unwrap();
+        assert_eq!(load(&path).unwrap(), [FIRST]);
+    }
+
+    #[test]
+    fn the_gateway_is_the_last_part_of_an_address() {
+        assert_eq!(gateway_of(&format!("nym://client.encryption@{}", FIRST)), Some(FIRST));
+        assert_eq!(gateway_of("client.encryption@"), None);
+        assert_eq!(gateway_of("no-gateway"), None);
+    }
+}
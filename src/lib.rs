@@ -0,0 +1,23 @@
+// The NymView server as a library, so the server binary and the end-to-end
+// tests in `tests/` build on the same modules.
+
+pub mod admin;
+pub mod admin_client;
+pub mod admission;
+pub mod assets;
+pub mod chunking;
+pub mod config;
+pub mod default_page;
+pub mod gateway;
+pub mod memory_transport;
+pub mod mixnet_server;
+pub mod pow;
+pub mod protocol;
+pub mod rate_limit;
+pub mod redirects;
+pub mod signing;
+pub mod site_cache;
+pub mod site_paths;
+pub mod site_watcher;
+pub mod transport;
+pub mod worker_pool;
@@ -1,6 +1,18 @@
 use eframe::egui;
 
+mod asset_loader;
+mod bookmarks;
+mod browser_data;
+mod chunking;
+mod gateway;
 mod mixnet_browser;
+mod petnames;
+mod pin_store;
+mod pow;
+mod protocol;
+mod session_history;
+mod signing;
+mod transport;
 
 fn main() -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
@@ -16,7 +28,10 @@ fn main() -> Result<(), eframe::Error> {
         options,
         Box::new(|cc| {
             cc.egui_ctx.set_visuals(egui::Visuals::light());
-            Ok(Box::new(mixnet_browser::NymMixnetBrowser::new()))
+            egui_extras::install_image_loaders(&cc.egui_ctx);
+            let browser = mixnet_browser::NymMixnetBrowser::new();
+            cc.egui_ctx.add_bytes_loader(browser.asset_loader());
+            Ok(Box::new(browser))
         }),
     )
 }
@@ -1,21 +1,172 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
-mod default_page;
-mod mixnet_server;
-mod config;
+use nymview::{admin_client, config, mixnet_server};
 
 #[derive(Parser)]
 #[command(name = "nym-view-server")]
 #[command(about = "NymView Server - Host MarkDown pages on the Nym Mixnet")]
 struct Cli {
-    #[arg(short, long, default_value = "./pages")]
-    directory: String,
+    /// Config file, by default server.toml in the NymView config directory
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Profile of the config file to run; repeat it to host several sites from one process
+    #[arg(short, long)]
+    profile: Vec<String>,
+
+    /// Host every profile of the config file
+    #[arg(long, conflicts_with = "profile")]
+    all_profiles: bool,
+
+    /// Pages directory [default: ./pages]
+    #[arg(short, long)]
+    directory: Option<PathBuf>,
+
+    /// Where the Nym client, site key and admin keys are kept
+    #[arg(long)]
+    identity_dir: Option<PathBuf>,
+
+    /// Identity key of the gateway to connect through; repeat it to pick one of several at random
+    #[arg(long)]
+    gateway: Vec<String>,
+
+    /// File with the public keys allowed to send admin commands [default: admin_keys in the identity directory]
+    #[arg(long)]
+    admin_keys: Option<PathBuf>,
+
+    /// Requests processed at the same time [default: 8]
+    #[arg(long)]
+    workers: Option<usize>,
+
+    /// Requests waiting for a worker before visitors are told the server is busy [default: 256]
+    #[arg(long)]
+    queue_size: Option<usize>,
+
+    /// Requests per second allowed from one visitor, 0 disables the limit [default: 2]
+    #[arg(long)]
+    rate_limit: Option<f64>,
+
+    /// Requests a visitor may make at once after a quiet period [default: 20]
+    #[arg(long)]
+    rate_burst: Option<f64>,
+
+    /// Requests per second allowed from all visitors together, 0 disables the limit [default: 50]
+    #[arg(long)]
+    global_rate_limit: Option<f64>,
+
+    /// Requests all visitors together may make at once [default: 200]
+    #[arg(long)]
+    global_rate_burst: Option<f64>,
+
+    /// Text sent to visitors who are over the limit
+    #[arg(long)]
+    rate_limit_message: Option<String>,
+
+    /// Proof-of-work difficulty in leading zero bits asked of visitors under load, 0 never asks [default: 20]
+    #[arg(long)]
+    pow_difficulty: Option<u8>,
+
+    /// Queued requests at which the server starts asking for proof of work [default: 64]
+    #[arg(long)]
+    pow_queue_threshold: Option<usize>,
+
+    /// Seconds proof of work stays required after the load has gone [default: 30]
+    #[arg(long)]
+    pow_cool_down: Option<u64>,
+
+    /// Print a line for every request served
+    #[arg(long)]
+    log_requests: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create an admin key pair; the public half goes into the server's admin_keys file
+    GenAdminKey {
+        #[arg(long, default_value = "admin.key")]
+        out: PathBuf,
+    },
+    /// Send a signed admin command (e.g. RELOAD) to a running server
+    Admin {
+        /// Nym address of the server
+        server: String,
+        command: String,
+        #[arg(default_value = "")]
+        path: String,
+        #[arg(short, long, default_value = "admin.key")]
+        key: PathBuf,
+    },
+}
+
+impl Cli {
+    // Flags given on the command line, to be laid over the config file
+    fn overrides(&self) -> config::Settings {
+        config::Settings {
+            pages_dir: self.directory.clone(),
+            identity_dir: self.identity_dir.clone(),
+            gateway: (!self.gateway.is_empty()).then(|| config::GatewaySetting::AnyOf(self.gateway.clone())),
+            admin_keys: self.admin_keys.clone(),
+            workers: self.workers,
+            queue_size: self.queue_size,
+            rate_limit: config::RateLimitSettings {
+                per_sender: self.rate_limit,
+                per_sender_burst: self.rate_burst,
+                global: self.global_rate_limit,
+                global_burst: self.global_rate_burst,
+                message: self.rate_limit_message.clone(),
+            },
+            pow: config::PowSettings {
+                difficulty: self.pow_difficulty,
+                queue_threshold: self.pow_queue_threshold,
+                cool_down: self.pow_cool_down,
+            },
+            logging: config::LoggingSettings {
+                requests: self.log_requests.then_some(true),
+                metrics_interval: None,
+            },
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
-    let mut server = mixnet_server::NymMixnetServer::new(&cli.directory).await?;
+    match &cli.command {
+        Some(Command::GenAdminKey { out }) => return admin_client::generate_key(out),
+        Some(Command::Admin { server, command, path, key }) => {
+            let response = admin_client::send_command(server, command, path, key).await?;
+            println!("{}: {}", response.status, response.body_text());
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let config_file = cli.config.clone().unwrap_or_else(config::default_config_file);
+    if config_file.exists() {
+        println!("Config file: {:?}", config_file);
+    }
+    let profiles = if cli.all_profiles {
+        config::Settings::profile_names(cli.config.as_deref())?
+    } else if cli.profile.is_empty() {
+        vec![config::DEFAULT_PROFILE.to_string()]
+    } else {
+        cli.profile.clone()
+    };
+    if profiles.is_empty() {
+        return Err(format!("No profiles in {}", config_file.display()).into());
+    }
+    println!("Profiles: {}", profiles.join(", "));
+
+    let mut server_configs = Vec::new();
+    for profile in &profiles {
+        let settings = config::Settings::load(cli.config.as_deref(), profile)?.merge(cli.overrides());
+        server_configs.push(settings.resolve(profile));
+    }
+    let server = mixnet_server::NymMixnetServer::new(&server_configs).await?;
     server.start().await?;
     Ok(())
 }
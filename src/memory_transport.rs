@@ -0,0 +1,204 @@
+// In-process stand-in for the mixnet.
+//
+// Endpoints created from one `MemoryNetwork` can message each other by
+// address. Like the mixnet, delivery can be delayed, reordered (jitter) or
+// dropped, and replies go through opaque tags instead of addresses.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::transport::{Incoming, Transport, TransportError, TransportSender};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemoryReplyTag(u64);
+
+type Mailbox = mpsc::UnboundedSender<Incoming<MemoryReplyTag>>;
+
+struct NetworkState {
+    mailboxes: HashMap<String, Mailbox>,
+    // Like sender tags on the mixnet, each sender keeps one tag
+    reply_tags: HashMap<MemoryReplyTag, String>,
+    sender_tags: HashMap<String, MemoryReplyTag>,
+    next_tag: u64,
+    latency: Duration,
+    jitter: Duration,
+    loss: f64,
+    rng: StdRng,
+}
+
+#[derive(Clone)]
+pub struct MemoryNetwork {
+    state: Arc<Mutex<NetworkState>>,
+}
+
+impl Default for MemoryNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryNetwork {
+    /// A lossless network that delivers immediately.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(NetworkState {
+                mailboxes: HashMap::new(),
+                reply_tags: HashMap::new(),
+                sender_tags: HashMap::new(),
+                next_tag: 0,
+                latency: Duration::ZERO,
+                jitter: Duration::ZERO,
+                loss: 0.0,
+                rng: StdRng::seed_from_u64(0),
+            })),
+        }
+    }
+
+    pub fn with_latency(self, latency: Duration) -> Self {
+        self.state.lock().unwrap().latency = latency;
+        self
+    }
+
+    /// Adds up to `jitter` of random extra delay, so messages can overtake each other.
+    pub fn with_jitter(self, jitter: Duration) -> Self {
+        self.state.lock().unwrap().jitter = jitter;
+        self
+    }
+
+    /// Drops each message with probability `loss`.
+    pub fn with_loss(self, loss: f64) -> Self {
+        self.state.lock().unwrap().loss = loss.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Seeds the randomness behind jitter and loss.
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.state.lock().unwrap().rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn endpoint(&self, address: &str) -> MemoryTransport {
+        let (mailbox, receiver) = mpsc::unbounded_channel();
+        self.state
+            .lock()
+            .unwrap()
+            .mailboxes
+            .insert(address.to_string(), mailbox);
+
+        MemoryTransport {
+            address: address.to_string(),
+            network: self.clone(),
+            receiver,
+        }
+    }
+
+    fn deliver(&self, to: &str, message: Vec<u8>, reply_to: Option<&str>) -> Result<(), TransportError> {
+        let mut state = self.state.lock().unwrap();
+        let mailbox = state
+            .mailboxes
+            .get(to)
+            .cloned()
+            .ok_or_else(|| TransportError(format!("Invalid address - unknown endpoint {}", to)))?;
+
+        let reply_tag = reply_to.map(|from| {
+            if let Some(tag) = state.sender_tags.get(from) {
+                return *tag;
+            }
+            let tag = MemoryReplyTag(state.next_tag);
+            state.next_tag += 1;
+            state.reply_tags.insert(tag, from.to_string());
+            state.sender_tags.insert(from.to_string(), tag);
+            tag
+        });
+
+        // Lost messages look delivered to the sender, as on the mixnet
+        let loss = state.loss;
+        if loss > 0.0 && state.rng.gen_bool(loss) {
+            return Ok(());
+        }
+        let mut delay = state.latency;
+        if !state.jitter.is_zero() {
+            let jitter = state.jitter;
+            delay += jitter.mul_f64(state.rng.gen::<f64>());
+        }
+        drop(state);
+
+        let incoming = Incoming { message, reply_tag };
+        if delay.is_zero() {
+            let _ = mailbox.send(incoming);
+        } else {
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = mailbox.send(incoming);
+            });
+        }
+        Ok(())
+    }
+
+    fn reply_address(&self, tag: &MemoryReplyTag) -> Result<String, TransportError> {
+        self.state
+            .lock()
+            .unwrap()
+            .reply_tags
+            .get(tag)
+            .cloned()
+            .ok_or_else(|| TransportError("Unknown reply tag".to_string()))
+    }
+}
+
+pub struct MemoryTransport {
+    address: String,
+    network: MemoryNetwork,
+    receiver: mpsc::UnboundedReceiver<Incoming<MemoryReplyTag>>,
+}
+
+impl Transport for MemoryTransport {
+    type ReplyTag = MemoryReplyTag;
+    type Sender = MemorySender;
+
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    fn sender(&self) -> MemorySender {
+        MemorySender {
+            address: self.address.clone(),
+            network: self.network.clone(),
+        }
+    }
+
+    async fn receive(&mut self) -> Option<Vec<Incoming<MemoryReplyTag>>> {
+        let mut messages = vec![self.receiver.recv().await?];
+        while let Ok(message) = self.receiver.try_recv() {
+            messages.push(message);
+        }
+        Some(messages)
+    }
+
+    async fn close(self) {
+        self.network.state.lock().unwrap().mailboxes.remove(&self.address);
+    }
+}
+
+#[derive(Clone)]
+pub struct MemorySender {
+    address: String,
+    network: MemoryNetwork,
+}
+
+impl TransportSender for MemorySender {
+    type ReplyTag = MemoryReplyTag;
+
+    async fn send(&self, address: &str, message: Vec<u8>) -> Result<(), TransportError> {
+        self.network.deliver(address.trim(), message, Some(&self.address))
+    }
+
+    async fn reply(&self, tag: &MemoryReplyTag, message: Vec<u8>) -> Result<(), TransportError> {
+        let to = self.network.reply_address(tag)?;
+        self.network.deliver(&to, message, None)
+    }
+}
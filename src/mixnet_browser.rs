@@ -1,73 +1,519 @@
 use nym_sdk::mixnet;
-use nym_sdk::mixnet::MixnetMessageSender;
 use egui::{Ui, TextEdit, ScrollArea, Color32};
 use tokio::sync::mpsc;
 use once_cell::sync::Lazy;
 use tokio::runtime::Runtime;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
 use eframe::App;
+use crate::asset_loader::NymAssetLoader;
+use crate::bookmarks::{self, Bookmark, BookmarkStore};
+use crate::browser_data;
+use crate::chunking::{self, ChunkAssembler};
+use crate::gateway::{self, GatewayChoice};
+use crate::protocol::{self, Request, Response, Status};
+use crate::petnames::{self, PetnameCard, PetnameStore};
+use crate::pin_store::{PinCheck, PinStore};
+use crate::pow::{self, Challenge};
+use crate::session_history::{HistoryEntry, SessionHistory};
+use crate::signing::{self, SignatureStatus};
+use crate::transport::{NymTransport, Transport, TransportSender};
 
 // Global runtime
 static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
     Runtime::new().expect("Failed to create Tokio runtime")
 });
 
+// Requests without an answer after this long are given up
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Missing chunks are requested again after this much silence
+const CHUNK_RETRY_AFTER: Duration = Duration::from_secs(15);
+const MAX_CHUNK_RETRIES: u32 = 3;
+
+// How often, and for how long at most, a page is requested again when the server says to slow down
+const MAX_SLOW_DOWN_RETRIES: u32 = 3;
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+// Challenges answered per request before giving up, the seed may rotate once
+const MAX_CHALLENGES: u32 = 2;
+
+// Redirects followed in a row before giving up
+const MAX_REDIRECTS: usize = 5;
+
+// Gateway choice in the browser data directory, see `gateway`
+const GATEWAYS_FILE: &str = "gateways";
+
+// Number of unmatched messages kept around for inspection
+const QUARANTINE_SIZE: usize = 32;
+
 // Global sender for requests
 static GUI_TO_MIXNET_SENDER: OnceLock<Arc<Mutex<Option<mpsc::UnboundedSender<BrowserMessage>>>>> =
     OnceLock::new();
 
 #[derive(Debug)]
 pub(crate) enum BrowserMessage {
-    SendRequest { recipient: String, message: String },
-    ReceivedMessage { content: String, from: String },
+    SendRequest { id: u64, recipient: String, payload: Vec<u8> },
+    ReceivedMessage { payload: Vec<u8>, from: String },
+    // Sending the request with this id failed
+    TransportError { id: u64, error: String },
     ConnectionStatus { status: String, loading: bool, client_address: String },
+    ChallengeSolved { id: u64, challenge: Challenge, nonce: u64 },
+}
+
+pub(crate) type TabId = u64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RequestPurpose {
+    // Page shown in the tab with this id
+    Page(TabId),
+    // Embedded resource requested by the image loader, keyed by its nym:// URI
+    Asset(String),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct InFlightRequest {
+    server: String,
+    path: String,
+    purpose: RequestPurpose,
+    sent_at: Instant,
+    assembler: Option<ChunkAssembler>,
+    retransmissions: u32,
+    reported_late: bool,
+    // Proof of work for a server under load, repeated with every resend
+    proof: Option<(Challenge, u64)>,
+    challenges: u32,
+    solving: bool,
+    // nym:// addresses that redirected here, oldest first
+    redirects: Vec<String>,
+}
+
+impl InFlightRequest {
+    fn last_activity(&self) -> Instant {
+        self.assembler
+            .as_ref()
+            .map_or(self.sent_at, |assembler| assembler.last_activity)
+    }
+
+    fn request(&self, id: u64) -> Request {
+        let mut request = Request::new(id, "GET", &self.path);
+        if let Some((challenge, nonce)) = &self.proof {
+            pow::attach(&mut request, challenge, *nonce);
+        }
+        request
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct QuarantinedMessage {
+    from: String,
+    reason: String,
+}
+
+// Page to request again once the server's retry-after hint has passed
+#[derive(Debug, Clone)]
+pub(crate) struct SlowDown {
+    server: String,
+    path: String,
+    // None once the retry has been sent
+    retry_at: Option<Instant>,
+    attempts: u32,
+    // Redirects that led to the page, kept for the retried request
+    redirects: Vec<String>,
+}
+
+// A page request the server turned down, shown in place of the page
+#[derive(Debug, Clone)]
+pub(crate) struct PageError {
+    server: String,
+    path: String,
+    status: Status,
+    // What the server said; None when the site sent an error page of its own,
+    // which is then the current content
+    message: Option<String>,
+}
+
+impl PageError {
+    /// Icon, title and advice shown for the status.
+    fn view(&self) -> (&'static str, &'static str, &'static str) {
+        match self.status {
+            Status::NotFound => ("🔍", "Page not found", "The site has no page at this address. Check it for typos or go back."),
+            Status::BadRequest => ("⚠", "Bad request", "The server could not make sense of the request; the address may be malformed."),
+            Status::Unauthorized => ("🔒", "Not allowed", "This needs to be signed by one of the site's admin keys."),
+            Status::TooLarge => ("📦", "Too large", "The answer is larger than the server will send over the mixnet."),
+            Status::RateLimited => ("⏳", "Too many requests", "The server asked to slow down several times. Wait a moment and try again."),
+            Status::ChallengeRequired => ("⏳", "Server under load", "The server kept asking for proof of work. Try again later."),
+            Status::Unavailable => ("⏳", "Server busy", "The server had no room to queue the request. Try again later."),
+            Status::ServerError => ("✖", "Server error", "Something went wrong on the server. Trying again may help."),
+            Status::Redirect => ("↪", "Page moved", "The page has moved, but the browser could not follow it."),
+            Status::Ok | Status::Other(_) => ("✖", "Unexpected answer", "The server answered with a status this browser does not know."),
+        }
+    }
+
+    // Worth asking again as is, the other statuses won't change by themselves
+    fn can_retry(&self) -> bool {
+        matches!(
+            self.status,
+            Status::RateLimited | Status::ChallengeRequired | Status::Unavailable | Status::ServerError
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GatewayMode {
+    Default,
+    Specific,
+    RandomFrom,
+}
+
+// Gateway choice being edited in the settings dialog
+#[derive(Debug, Clone)]
+pub(crate) struct GatewaySettings {
+    mode: GatewayMode,
+    specific: String,
+    candidates: String,
+    error: Option<String>,
+}
+
+impl GatewaySettings {
+    fn new(choice: &GatewayChoice) -> Self {
+        let (mode, specific, candidates) = match choice {
+            GatewayChoice::Default => (GatewayMode::Default, String::new(), String::new()),
+            GatewayChoice::Specific(key) => (GatewayMode::Specific, key.clone(), String::new()),
+            GatewayChoice::RandomFrom(keys) => (GatewayMode::RandomFrom, String::new(), keys.join("\n")),
+        };
+        Self { mode, specific, candidates, error: None }
+    }
+
+    fn choice(&self) -> Result<GatewayChoice, String> {
+        let choice = match self.mode {
+            GatewayMode::Default => GatewayChoice::Default,
+            GatewayMode::Specific => match GatewayChoice::from_keys(vec![self.specific.clone()]) {
+                GatewayChoice::Default => return Err("Enter the identity key of a gateway".to_string()),
+                choice => choice,
+            },
+            GatewayMode::RandomFrom => {
+                let keys = self.candidates.split_whitespace().map(str::to_string).collect();
+                match GatewayChoice::from_keys(keys) {
+                    GatewayChoice::Default => return Err("Enter at least one gateway identity key".to_string()),
+                    choice => choice,
+                }
+            }
+        };
+        choice.validate()?;
+        Ok(choice)
+    }
+}
+
+// Bookmark being edited; `index` is None for a new one
+#[derive(Debug, Clone)]
+pub(crate) struct BookmarkDraft {
+    index: Option<usize>,
+    title: String,
+    url: String,
+    folder: String,
+    tags: String,
+    added: u64,
+    error: Option<String>,
+}
+
+impl BookmarkDraft {
+    fn new(index: Option<usize>, bookmark: &Bookmark) -> Self {
+        Self {
+            index,
+            title: bookmark.title.clone(),
+            url: bookmark.url.clone(),
+            folder: bookmark.folder.clone(),
+            tags: bookmark.tags.join(", "),
+            added: bookmark.added,
+            error: None,
+        }
+    }
+
+    fn bookmark(&self) -> Result<Bookmark, String> {
+        let url = self.url.trim();
+        if NymMixnetBrowser::parse_nym_url(url).is_none_or(|(server, _)| server.is_empty()) {
+            return Err("The address must start with nym://".to_string());
+        }
+        let title = if self.title.trim().is_empty() { url } else { self.title.trim() };
+        Ok(Bookmark {
+            folder: bookmarks::normalize_folder(&self.folder),
+            tags: bookmarks::parse_tags(&self.tags),
+            added: self.added,
+            ..Bookmark::new(title, url)
+        })
+    }
+}
+
+// State of the bookmark manager window
+#[derive(Debug, Clone)]
+pub(crate) struct BookmarkManager {
+    search: String,
+    // File to import from or export to
+    transfer_path: String,
+    passphrase: String,
+    message: Option<String>,
+}
+
+impl BookmarkManager {
+    fn new() -> Self {
+        let transfer_path = dirs::home_dir()
+            .unwrap_or_default()
+            .join("nymview-bookmarks.json");
+        Self {
+            search: String::new(),
+            transfer_path: transfer_path.display().to_string(),
+            passphrase: String::new(),
+            message: None,
+        }
+    }
+}
+
+enum BookmarkAction {
+    Open(String, bool),
+    Edit(usize),
+    Remove(usize),
+    Unlock,
+    Import,
+    Export,
+    SetPassphrase,
+    RemovePassphrase,
+}
+
+// Petname fields of the site info window
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PetnameForm {
+    // Server the name field was filled in for
+    server: String,
+    name: String,
+    card: String,
+    message: Option<String>,
+}
+
+enum PetnameAction {
+    Save,
+    Remove(String),
+    CopyCard,
+    ImportCard,
+}
+
+// Where a link on a page leads
+#[derive(Debug, Clone, PartialEq)]
+enum LinkTarget {
+    // A page on the site shown
+    Page(String),
+    // A full `nym://` address, possibly of another server
+    Address(String),
+    // Anything outside the mixnet
+    External(String),
 }
 
+// A page held back because it was not signed by the key pinned for its server
 #[derive(Debug, Clone)]
-pub(crate) struct HistoryEntry {
+pub(crate) struct PinWarning {
     server: String,
-    page: String,
+    path: String,
+    pinned: String,
+    // None when the page was not signed at all
+    presented: Option<String>,
+    response: Response,
+}
+
+// One browser tab: its own page, address bar and history. All tabs share
+// the mixnet connection, and answers are routed back by the tab's id.
+pub(crate) struct Tab {
+    id: TabId,
+    address_bar: String,
+    server_address: String,
+    current_content: String,
+    page_loading: bool,
+    error: Option<String>,
+    history: SessionHistory,
+    md_cache: CommonMarkCache,
+    // The page request this tab is waiting for, answers to any other are late
+    active_request: Option<u64>,
+    notice: Option<String>,
+    // Signature check of the page currently shown
+    page_signature: Option<SignatureStatus>,
+    // Set while the page shown is an error rather than what was asked for
+    page_error: Option<PageError>,
+    // Redirects that led to the page being shown
+    redirect_chain: Vec<String>,
+    // External link waiting to be confirmed, it would leave the mixnet
+    external_link: Option<String>,
+    pin_warning: Option<PinWarning>,
+    slow_down: Option<SlowDown>,
+}
+
+impl Tab {
+    fn new(id: TabId) -> Self {
+        Self {
+            id,
+            address_bar: String::new(),
+            server_address: String::new(),
+            current_content: String::new(),
+            page_loading: false,
+            error: None,
+            history: SessionHistory::default(),
+            md_cache: CommonMarkCache::default(),
+            active_request: None,
+            notice: None,
+            page_signature: None,
+            page_error: None,
+            redirect_chain: Vec::new(),
+            external_link: None,
+            pin_warning: None,
+            slow_down: None,
+        }
+    }
+
+    /// Label in the tab strip: the page's first heading, or else its address.
+    fn title(&self) -> String {
+        if let Some(page_error) = &self.page_error {
+            return page_error.view().1.to_string();
+        }
+        let heading = self
+            .current_content
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("# "))
+            .map(str::trim)
+            .filter(|heading| !heading.is_empty());
+        match heading {
+            Some(heading) => heading.to_string(),
+            None if self.page_loading && self.current_content.is_empty() => "Loading...".to_string(),
+            None if !self.address_bar.trim().is_empty() => self.address_bar.trim().to_string(),
+            None if !self.server_address.is_empty() => gateway::short(&self.server_address),
+            None => "New tab".to_string(),
+        }
+    }
+
+    fn parse_and_set_url(&mut self, url: &str) {
+        if let Some((server, page)) = NymMixnetBrowser::parse_nym_url(url) {
+            self.server_address = server.trim().to_string();
+            self.address_bar = if page.is_empty() { String::new() } else { page };
+        } else {
+            self.address_bar = url.trim().to_string();
+        }
+    }
+
+    // Full address of the page in the address bar
+    fn url(&self) -> Option<String> {
+        let server = self.server_address.trim();
+        (!server.is_empty()).then(|| format!("nym://{}/{}", server, self.address_bar.trim_start_matches('/')))
+    }
+
+    // Path of the page in the address bar, as sent to the server
+    fn request_path(&self) -> String {
+        if self.address_bar.is_empty() {
+            "/".to_string()
+        } else if self.address_bar.starts_with('/') {
+            self.address_bar.clone()
+        } else {
+            format!("/{}", self.address_bar)
+        }
+    }
+
+    // Records the page in the address bar as a new step in the history
+    fn visit(&mut self) {
+        // Going back should skip error pages, they were never a real destination
+        if self.page_error.is_some() {
+            self.history.forget_current();
+        }
+        self.history.visit(HistoryEntry::new(&self.server_address, &self.address_bar));
+    }
+}
+
+impl Clone for Tab {
+    fn clone(&self) -> Self {
+        Self {
+            address_bar: self.address_bar.clone(),
+            server_address: self.server_address.clone(),
+            current_content: self.current_content.clone(),
+            error: self.error.clone(),
+            history: self.history.clone(),
+            page_signature: self.page_signature.clone(),
+            page_error: self.page_error.clone(),
+            redirect_chain: self.redirect_chain.clone(),
+            ..Self::new(self.id)
+        }
+    }
 }
 
 pub struct NymMixnetBrowser {
-    pub address_bar: String,
-    pub current_content: String,
     pub loading: bool,
-    pub page_loading: bool,
-    pub error: Option<String>,
     pub connection_status: String,
-    pub server_address: String,
     pub client_address: String,
     pub(crate) message_receiver: Option<mpsc::UnboundedReceiver<BrowserMessage>>,
     pub(crate) message_sender: Option<mpsc::UnboundedSender<BrowserMessage>>,
-    pub(crate) history: Vec<HistoryEntry>,
     pub(crate) connection_attempted: bool,
-    pub(crate) md_cache: CommonMarkCache,
-    pub(crate) pending_navigation: Option<String>,
+    pub(crate) tabs: Vec<Tab>,
+    pub(crate) active_tab: TabId,
+    pub(crate) next_tab_id: TabId,
+    // Link clicked on the current page, followed on the next frame
+    pub(crate) pending_navigation: Option<egui::OpenUrl>,
+    pub(crate) in_flight: HashMap<u64, InFlightRequest>,
+    pub(crate) quarantine: VecDeque<QuarantinedMessage>,
+    pub(crate) asset_loader: Arc<NymAssetLoader>,
+    pub(crate) pins: PinStore,
+    pub(crate) show_site_info: bool,
+    pub(crate) gateway_choice: GatewayChoice,
+    // Open while the settings dialog is shown
+    pub(crate) settings: Option<GatewaySettings>,
+    pub(crate) bookmarks: BookmarkStore,
+    pub(crate) bookmark_manager: Option<BookmarkManager>,
+    pub(crate) bookmark_draft: Option<BookmarkDraft>,
+    pub(crate) petnames: PetnameStore,
+    pub(crate) petname_form: PetnameForm,
 }
 
 impl NymMixnetBrowser {
     pub fn new() -> Self {
         Self {
-            address_bar: String::new(),
-            current_content: String::new(),
             loading: true,
-            page_loading: false,
-            error: None,
             connection_status: "Connecting to Mixnet...".to_string(),
-            server_address: String::new(),
             client_address: String::new(),
             message_receiver: None,
             message_sender: None,
-            history: Vec::new(),
             connection_attempted: false,
-            md_cache: CommonMarkCache::default(),
+            tabs: vec![Tab::new(0)],
+            active_tab: 0,
+            next_tab_id: 1,
             pending_navigation: None,
+            in_flight: HashMap::new(),
+            quarantine: VecDeque::new(),
+            asset_loader: Arc::new(NymAssetLoader::default()),
+            pins: PinStore::load(),
+            show_site_info: false,
+            gateway_choice: Self::load_gateway_choice(),
+            settings: None,
+            bookmarks: BookmarkStore::load(),
+            bookmark_manager: None,
+            bookmark_draft: None,
+            petnames: PetnameStore::load(),
+            petname_form: PetnameForm::default(),
         }
     }
 
+    fn load_gateway_choice() -> GatewayChoice {
+        let keys = gateway::load(&browser_data::get_data_dir().join(GATEWAYS_FILE)).unwrap_or_else(|e| {
+            eprintln!("Error reading gateway settings: {}", e);
+            Vec::new()
+        });
+        GatewayChoice::from_keys(keys)
+    }
+
+    fn save_gateway_choice(choice: &GatewayChoice) -> std::io::Result<()> {
+        let dir = browser_data::ensure_data_dir()?;
+        gateway::save(&dir.join(GATEWAYS_FILE), choice.keys())
+    }
+
+    pub fn asset_loader(&self) -> Arc<NymAssetLoader> {
+        self.asset_loader.clone()
+    }
+
     pub fn init(&mut self) {
         if !self.connection_attempted {
             let (tx, rx) = mpsc::unbounded_channel::<BrowserMessage>();
@@ -80,8 +526,9 @@ impl NymMixnetBrowser {
 
     fn start_connection(&mut self) {
         if let Some(sender) = self.message_sender.clone() {
+            let gateway_choice = self.gateway_choice.clone();
             RUNTIME.spawn(async move {
-                match Self::connect_with_status(sender).await {
+                match Self::connect_with_status(sender, gateway_choice).await {
                     Ok(_) => println!("Connection successful"),
                     Err(e) => eprintln!("Connection failed: {}", e),
                 }
@@ -89,15 +536,23 @@ impl NymMixnetBrowser {
         }
     }
 
-    async fn connect_with_status(sender: mpsc::UnboundedSender<BrowserMessage>) -> Result<(), String> {
+    async fn connect_with_status(
+        sender: mpsc::UnboundedSender<BrowserMessage>,
+        gateway_choice: GatewayChoice,
+    ) -> Result<(), String> {
         let _ = sender.send(BrowserMessage::ConnectionStatus {
-            status: "Connecting to Mixnet...".to_string(),
+            status: format!("Connecting to Mixnet ({})...", gateway_choice.describe()),
             loading: true,
             client_address: String::new(),
         });
 
         println!("Creating Mixnet Client...");
-        let client = mixnet::MixnetClientBuilder::new_ephemeral()
+        let mut builder = mixnet::MixnetClientBuilder::new_ephemeral();
+        if let Some(gateway) = gateway_choice.pick(None) {
+            println!("Requesting gateway {}", gateway);
+            builder = builder.request_gateway(gateway);
+        }
+        let client = builder
             .build()
             .map_err(|e| format!("Client creation error: {}", e))?;
 
@@ -121,7 +576,7 @@ impl NymMixnetBrowser {
         *GUI_TO_MIXNET_SENDER.get().unwrap().lock().unwrap() = Some(gui_to_mixnet_tx);
 
         RUNTIME.spawn(Self::mixnet_task(
-            connected_client,
+            NymTransport::new(connected_client),
             gui_to_mixnet_rx,
             sender,
         ));
@@ -129,54 +584,45 @@ impl NymMixnetBrowser {
         Ok(())
     }
 
-    async fn mixnet_task(
-        mut client: mixnet::MixnetClient,
+    async fn mixnet_task<T: Transport>(
+        mut transport: T,
         mut from_gui: mpsc::UnboundedReceiver<BrowserMessage>,
         to_gui: mpsc::UnboundedSender<BrowserMessage>,
     ) {
+        let sender = transport.sender();
         loop {
             tokio::select! {
-                messages = client.wait_for_messages() => {
+                messages = transport.receive() => {
                     if let Some(messages) = messages {
                         for received in messages {
-                            let text_message = String::from_utf8_lossy(&received.message).into_owned();
-                            let sender_info = if let Some(sender_tag) = &received.sender_tag {
-                                format!("{:?}", sender_tag)
+                            let sender_info = if let Some(reply_tag) = &received.reply_tag {
+                                format!("{:?}", reply_tag)
                             } else {
                                 "unknown".to_string()
                             };
                             let _ = to_gui.send(BrowserMessage::ReceivedMessage {
-                                content: text_message,
+                                payload: received.message,
                                 from: sender_info,
                             });
                         }
                     }
                 }
-                Some(gui_message) = from_gui.recv() => {
-                    if let BrowserMessage::SendRequest { recipient, message } = gui_message {
-                        match recipient.parse::<nym_sdk::mixnet::Recipient>() {
-                            Ok(recipient_addr) => {
-                                if let Err(e) = client.send_plain_message(recipient_addr, message).await {
-                                    eprintln!("Error sending: {}", e);
-                                    let _ = to_gui.send(BrowserMessage::ReceivedMessage {
-                                        content: format!("ERROR: {}", e),
-                                        from: "system".to_string(),
-                                    });
-                                }
-                                // Keine "Request sent successfully" Ausgabe mehr
-                            }
-                            Err(e) => {
-                                eprintln!("Invalid recipient address: {}", e);
-                                let _ = to_gui.send(BrowserMessage::ReceivedMessage {
-                                    content: format!("ERROR: Invalid address - {}", e),
-                                    from: "system".to_string(),
-                                });
-                            }
+                gui_message = from_gui.recv() => {
+                    // The GUI dropped its end for a new connection
+                    let Some(gui_message) = gui_message else { break };
+                    if let BrowserMessage::SendRequest { id, recipient, payload } = gui_message {
+                        if let Err(e) = sender.send(&recipient, payload).await {
+                            eprintln!("Error sending: {}", e);
+                            let _ = to_gui.send(BrowserMessage::TransportError {
+                                id,
+                                error: e.to_string(),
+                            });
                         }
                     }
                 }
             }
         }
+        transport.close().await;
     }
 
     fn get_gui_sender() -> Option<mpsc::UnboundedSender<BrowserMessage>> {
@@ -185,43 +631,148 @@ impl NymMixnetBrowser {
             .and_then(|arc| arc.lock().unwrap().clone())
     }
 
-    pub fn send_request(&self, request_path: &str) -> Result<(), String> {
-        let recipient = self.server_address.trim();
+    fn tab(&self) -> &Tab {
+        self.tabs
+            .iter()
+            .find(|tab| tab.id == self.active_tab)
+            .unwrap_or(&self.tabs[0])
+    }
+
+    fn tab_mut(&mut self, id: TabId) -> Option<&mut Tab> {
+        self.tabs.iter_mut().find(|tab| tab.id == id)
+    }
+
+    fn tab_failed(&mut self, tab: TabId, error: String) {
+        if let Some(tab) = self.tab_mut(tab) {
+            tab.error = Some(error);
+            tab.page_loading = false;
+        }
+    }
+
+    /// Opens an empty tab after the current one and returns its id.
+    fn open_tab(&mut self) -> TabId {
+        let id = self.next_tab_id;
+        self.next_tab_id += 1;
+        let position = self
+            .tabs
+            .iter()
+            .position(|tab| tab.id == self.active_tab)
+            .map_or(self.tabs.len(), |index| index + 1);
+        self.tabs.insert(position, Tab::new(id));
+        id
+    }
+
+    fn close_tab(&mut self, id: TabId) {
+        let Some(index) = self.tabs.iter().position(|tab| tab.id == id) else { return };
+        self.tabs.remove(index);
+        // Nobody is left to show what is still on its way
+        self.in_flight.retain(|_, request| request.purpose != RequestPurpose::Page(id));
+
+        if self.tabs.is_empty() {
+            let id = self.open_tab();
+            self.active_tab = id;
+        } else if self.active_tab == id {
+            self.active_tab = self.tabs[index.min(self.tabs.len() - 1)].id;
+        }
+    }
+
+    fn move_tab(&mut self, id: TabId, to: usize) {
+        let Some(from) = self.tabs.iter().position(|tab| tab.id == id) else { return };
+        let tab = self.tabs.remove(from);
+        self.tabs.insert(to.min(self.tabs.len()), tab);
+    }
+
+    // Index of the active tab moved by `step`, wrapping around
+    fn tab_index_after(&self, step: isize) -> usize {
+        let count = self.tabs.len() as isize;
+        let current = self.tabs.iter().position(|tab| tab.id == self.active_tab).unwrap_or(0) as isize;
+        (current + step).rem_euclid(count) as usize
+    }
+
+    pub fn send_request(&mut self, tab: TabId, request_path: &str) -> Result<u64, String> {
+        let recipient = self
+            .tab_mut(tab)
+            .map(|tab| tab.server_address.trim().to_string())
+            .unwrap_or_default();
+        let id = self.start_request(recipient, request_path, RequestPurpose::Page(tab))?;
+
+        // Any earlier request is now stale; its reply will be reported as late
+        if let Some(tab) = self.tab_mut(tab) {
+            tab.active_request = Some(id);
+            tab.slow_down = None;
+        }
+        Ok(id)
+    }
+
+    fn start_request(&mut self, recipient: String, request_path: &str, purpose: RequestPurpose) -> Result<u64, String> {
         if recipient.is_empty() {
             return Err("No server address specified".to_string());
         }
-        let my_address = self.client_address.trim();
-        if my_address.is_empty() {
+        if self.client_address.trim().is_empty() {
             return Err("Not connected yet - waiting for client address".to_string());
         }
 
-        let request = format!("GET {} FROM {}", request_path, my_address);
+        // No FROM address: the server answers through the SURBs we attach.
+        // Random IDs keep other senders from guessing which reply we expect.
+        let id = rand::random::<u64>();
+        Self::dispatch(&recipient, &Request::new(id, "GET", request_path))?;
 
-        if let Some(sender) = Self::get_gui_sender() {
-            sender.send(BrowserMessage::SendRequest {
-                recipient: recipient.to_string(),
-                message: request,
-            }).map_err(|e| format!("Send error: {}", e))?;
+        self.in_flight.insert(id, InFlightRequest {
+            server: recipient,
+            path: request_path.to_string(),
+            purpose,
+            sent_at: Instant::now(),
+            assembler: None,
+            retransmissions: 0,
+            reported_late: false,
+            proof: None,
+            challenges: 0,
+            solving: false,
+            redirects: Vec::new(),
+        });
+        Ok(id)
+    }
+
+    /// Requests an asset for a page of `page_server`, which relative URIs point to.
+    fn fetch_asset(&mut self, uri: &str, page_server: &str) {
+        let Some((host, page)) = Self::parse_nym_url(&self.petnames.resolve_url(uri)) else {
+            self.asset_loader.finish(uri, Err("Not a nym:// URI".to_string()));
+            return;
+        };
+
+        // Full addresses always contain '@'; anything else is a path on the page's server
+        let page_server = page_server.to_string();
+        let (server, path) = if host.contains('@') {
+            (host, format!("/{}", page))
+        } else if page.is_empty() {
+            (page_server, format!("/{}", host))
         } else {
-            return Err("Not connected to Mixnet".to_string());
+            (page_server, format!("/{}/{}", host, page))
+        };
+
+        if let Err(e) = self.start_request(server, &path, RequestPurpose::Asset(uri.to_string())) {
+            self.asset_loader.finish(uri, Err(e));
         }
-        Ok(())
     }
 
-    fn parse_and_set_url(&mut self, url: &str) {
-        if let Some((server, page)) = Self::parse_nym_url(url) {
-            self.server_address = server.trim().to_string();
-            self.address_bar = if page.is_empty() { String::new() } else { page };
+    fn dispatch(recipient: &str, request: &Request) -> Result<(), String> {
+        let payload = request
+            .encode()
+            .map_err(|e| format!("Invalid request: {}", e))?;
+
+        if let Some(sender) = Self::get_gui_sender() {
+            sender.send(BrowserMessage::SendRequest {
+                id: request.id,
+                recipient: recipient.to_string(),
+                payload,
+            }).map_err(|e| format!("Send error: {}", e))
         } else {
-            self.address_bar = url.trim().to_string();
+            Err("Not connected to Mixnet".to_string())
         }
     }
 
     fn parse_nym_url(url: &str) -> Option<(String, String)> {
-        if !url.starts_with("nym://") {
-            return None;
-        }
-        let without_protocol = &url[6..];
+        let without_protocol = url.strip_prefix("nym://")?;
         if let Some(slash_pos) = without_protocol.find('/') {
             let server = without_protocol[..slash_pos].to_string();
             let page = without_protocol.get(slash_pos + 1..).unwrap_or("").to_string();
@@ -231,25 +782,133 @@ impl NymMixnetBrowser {
         }
     }
 
-    fn handle_navigation(&mut self) {
-        let address = self.address_bar.clone();
-        self.parse_and_set_url(&address);
-        self.page_loading = true;
+    fn handle_navigation(&mut self, tab: TabId) {
+        let Some(current) = self.tabs.iter_mut().find(|current| current.id == tab) else { return };
+        let address = self.petnames.resolve_url(&current.address_bar);
+        current.parse_and_set_url(&address);
+        if self.load_address(tab) {
+            if let Some(current) = self.tab_mut(tab) {
+                current.visit();
+            }
+        }
+    }
 
-        let path = if self.address_bar.is_empty() {
-            "/".to_string()
-        } else if self.address_bar.starts_with('/') {
-            self.address_bar.clone()
-        } else {
-            format!("/{}", self.address_bar)
-        };
+    // Requests the page in the tab's address bar, returning whether the request went out
+    fn load_address(&mut self, tab: TabId) -> bool {
+        let Some(current) = self.tab_mut(tab) else { return false };
+        current.page_loading = true;
+        let path = current.request_path();
 
-        match self.send_request(&path) {
-            Ok(()) => {},
+        match self.send_request(tab, &path) {
+            Ok(_) => true,
             Err(e) => {
-                self.error = Some(e);
-                self.page_loading = false;
+                self.tab_failed(tab, e);
+                false
+            }
+        }
+    }
+
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        use egui::{Key, Modifiers};
+        // Alt+arrows move by words in a text field, they are left to it there
+        let typing = ctx.wants_keyboard_input();
+        let (new_tab, close_tab, next, previous, move_left, move_right, back, forward, bookmark) = ctx.input_mut(|input| {
+            // consume_key ignores extra Shift, so the Shift shortcuts go first
+            let previous = input.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::Tab);
+            let move_left = input.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::PageUp);
+            let move_right = input.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::PageDown);
+            (
+                input.consume_key(Modifiers::COMMAND, Key::T),
+                input.consume_key(Modifiers::COMMAND, Key::W),
+                input.consume_key(Modifiers::COMMAND, Key::Tab) || input.consume_key(Modifiers::COMMAND, Key::PageDown),
+                previous || input.consume_key(Modifiers::COMMAND, Key::PageUp),
+                move_left,
+                move_right,
+                !typing && input.consume_key(Modifiers::ALT, Key::ArrowLeft),
+                !typing && input.consume_key(Modifiers::ALT, Key::ArrowRight),
+                input.consume_key(Modifiers::COMMAND, Key::D),
+            )
+        });
+
+        if bookmark && self.tab().url().is_some() {
+            self.edit_page_bookmark();
+        }
+
+        if back || forward {
+            self.traverse(self.active_tab, if back { -1 } else { 1 });
+        }
+
+        if new_tab {
+            self.active_tab = self.open_tab();
+        }
+        if close_tab {
+            self.close_tab(self.active_tab);
+        }
+        if next || previous {
+            let index = self.tab_index_after(if next { 1 } else { -1 });
+            self.active_tab = self.tabs[index].id;
+        }
+        if move_left || move_right {
+            let index = self.tab_index_after(if move_right { 1 } else { -1 });
+            self.move_tab(self.active_tab, index);
+        }
+    }
+
+    fn show_tab_strip(&mut self, ui: &mut Ui) {
+        let mut select = None;
+        let mut close = None;
+        let mut moved = None;
+        let mut new_tab = false;
+
+        ui.horizontal_wrapped(|ui| {
+            for (index, tab) in self.tabs.iter().enumerate() {
+                let mut title = tab.title();
+                if title.chars().count() > 24 {
+                    title = format!("{}…", title.chars().take(23).collect::<String>());
+                }
+                if tab.page_loading {
+                    title = format!("⟳ {}", title);
+                }
+
+                let response = ui
+                    .add(egui::Button::selectable(tab.id == self.active_tab, title).sense(egui::Sense::click_and_drag()))
+                    .on_hover_text(format!(
+                        "nym://{}/{}",
+                        self.petnames.name_of(&tab.server_address).unwrap_or(&tab.server_address),
+                        tab.address_bar
+                    ));
+                if response.clicked() {
+                    select = Some(tab.id);
+                }
+                if response.middle_clicked() {
+                    close = Some(tab.id);
+                }
+                // Tabs are reordered by dragging one onto another
+                response.dnd_set_drag_payload(tab.id);
+                if let Some(dragged) = response.dnd_release_payload::<TabId>() {
+                    moved = Some((*dragged, index));
+                }
+                if ui.small_button("✕").on_hover_text("Close tab (Ctrl+W)").clicked() {
+                    close = Some(tab.id);
+                }
+                ui.separator();
             }
+            if ui.button("+").on_hover_text("New tab (Ctrl+T)").clicked() {
+                new_tab = true;
+            }
+        });
+
+        if let Some(id) = select {
+            self.active_tab = id;
+        }
+        if let Some((id, to)) = moved {
+            self.move_tab(id, to);
+        }
+        if let Some(id) = close {
+            self.close_tab(id);
+        }
+        if new_tab {
+            self.active_tab = self.open_tab();
         }
     }
 
@@ -261,8 +920,19 @@ impl NymMixnetBrowser {
         }
 
         // Process pending navigation first
-        if let Some(url) = self.pending_navigation.take() {
-            self.handle_link_click(&url);
+        if let Some(open_url) = self.pending_navigation.take() {
+            if open_url.new_tab {
+                // Middle or Ctrl click: follow the link in a new tab behind this one
+                let (server, page) = (self.tab().server_address.clone(), self.tab().address_bar.clone());
+                let tab = self.open_tab();
+                if let Some(new_tab) = self.tab_mut(tab) {
+                    new_tab.server_address = server;
+                    new_tab.address_bar = page;
+                }
+                self.handle_link_click(tab, &open_url.url);
+            } else {
+                self.handle_link_click(self.active_tab, &open_url.url);
+            }
         }
 
         let mut messages_to_process = Vec::new();
@@ -274,8 +944,12 @@ impl NymMixnetBrowser {
 
         for message in messages_to_process {
             match message {
-                BrowserMessage::ReceivedMessage { content, from } => {
-                    self.handle_server_message(content, from);
+                BrowserMessage::ReceivedMessage { payload, from } => {
+                    self.handle_server_message(&payload, from);
+                }
+                BrowserMessage::TransportError { id, error } => {
+                    // Shown on the tab that sent the request, not the one in front
+                    self.fail_request(id, error);
                 }
                 BrowserMessage::ConnectionStatus { status, loading, client_address } => {
                     self.connection_status = status;
@@ -284,10 +958,25 @@ impl NymMixnetBrowser {
                         self.client_address = client_address;
                     }
                 }
+                BrowserMessage::ChallengeSolved { id, challenge, nonce } => {
+                    self.send_solution(id, challenge, nonce);
+                }
                 _ => {}
             }
         }
 
+        for (uri, server) in self.asset_loader.take_requests() {
+            self.fetch_asset(&uri, &server);
+        }
+
+        self.check_requests();
+        self.check_slow_down();
+        if !self.in_flight.is_empty() || self.tabs.iter().any(|tab| tab.slow_down.is_some()) {
+            ui.ctx().request_repaint_after(Duration::from_millis(250));
+        }
+
+        self.handle_shortcuts(&ui.ctx().clone());
+
         // Status line
         ui.horizontal(|ui| {
             ui.label("Status:");
@@ -296,225 +985,1377 @@ impl NymMixnetBrowser {
                 ui.spinner();
                 ui.colored_label(Color32::BLUE, "Connecting...");
             }
+            if let Some(gateway) = gateway::gateway_of(&self.client_address) {
+                ui.colored_label(Color32::GRAY, format!("via gateway {}", gateway::short(gateway)))
+                    .on_hover_text(gateway);
+            }
+            if ui.small_button("⚙").on_hover_text("Settings").clicked() && self.settings.is_none() {
+                self.settings = Some(GatewaySettings::new(&self.gateway_choice));
+            }
+            if !self.quarantine.is_empty() {
+                let label = ui.colored_label(
+                    Color32::GRAY,
+                    format!("{} stray message(s) dropped", self.quarantine.len()),
+                );
+                label.on_hover_ui(|ui| {
+                    for message in &self.quarantine {
+                        ui.label(format!("{} (from {})", message.reason, message.from));
+                    }
+                });
+            }
         });
 
+        ui.separator();
+        self.show_tab_strip(ui);
         ui.separator();
 
+        let tab_id = self.active_tab;
+        let mut go = false;
+        let mut traverse = None;
+
         // Address bar with responsive design
         ui.horizontal(|ui| {
-            if ui.button("←").clicked() && self.history.len() > 1 {
-                self.go_back();
+            let back: Vec<HistoryEntry> = self.tab().history.back_entries().cloned().collect();
+            let forward: Vec<HistoryEntry> = self.tab().history.forward_entries().cloned().collect();
+            if let Some(steps) = history_button(ui, "←", "Back (Alt+Left), hold for history", &back) {
+                traverse = Some(-(steps as isize));
             }
-            
+            if let Some(steps) = history_button(ui, "→", "Forward (Alt+Right), hold for history", &forward) {
+                traverse = Some(steps as isize);
+            }
+
             ui.label("Address:");
-            
+            self.show_signature_indicator(ui);
+            if ui.small_button("ℹ").on_hover_text("Site info").clicked() {
+                self.show_site_info = !self.show_site_info;
+            }
+            if let Some(url) = self.tab().url() {
+                let star = if self.bookmarks.find(&url).is_some() { "★" } else { "☆" };
+                if ui.small_button(star).on_hover_text("Bookmark this page (Ctrl+D)").clicked() {
+                    self.edit_page_bookmark();
+                }
+            }
+
+            let connecting = self.loading;
+            let Some(tab) = self.tab_mut(tab_id) else { return };
+
             // Longer text field that adapts
             let text_width = ui.available_width() - 120.0;
             let response = ui.add(
-                TextEdit::singleline(&mut self.address_bar)
+                TextEdit::singleline(&mut tab.address_bar)
                     .hint_text("nym://server/page")
                     .desired_width(text_width)
                     .min_size(egui::Vec2::new(550.0, 0.0))
             );
 
-            let can_navigate = !self.loading && !self.address_bar.trim().is_empty();
-            
+            let can_navigate = !connecting && !tab.address_bar.trim().is_empty();
+
             // Align buttons to the right
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 if ui.button("Go").clicked() && can_navigate {
-                    self.handle_navigation();
+                    go = true;
                 }
-                
-                if self.page_loading {
+
+                if tab.page_loading {
                     ui.spinner();
                 }
             });
 
             // Enter key handling
             if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) && can_navigate {
-                self.handle_navigation();
+                go = true;
             }
         });
 
-        if let Some(ref err) = self.error {
+        if let Some(offset) = traverse {
+            self.traverse(tab_id, offset);
+        } else if go {
+            self.handle_navigation(tab_id);
+        }
+
+        if let Some(err) = &self.tab().error {
             ui.colored_label(Color32::BLUE, err);
         }
 
-        ScrollArea::vertical().show(ui, |ui| {
-            if self.page_loading {
+        if let Some(notice) = self.tab().notice.clone() {
+            ui.horizontal(|ui| {
+                ui.colored_label(Color32::GRAY, notice);
+                if ui.small_button("✕").clicked() {
+                    if let Some(tab) = self.tab_mut(tab_id) {
+                        tab.notice = None;
+                    }
+                }
+            });
+        }
+
+        self.show_bookmarks_bar(ui);
+        self.show_external_link(ui);
+        self.show_pin_warning(ui);
+        if self.show_site_info {
+            self.show_site_info(&ui.ctx().clone());
+        }
+        self.show_settings(&ui.ctx().clone());
+        self.show_bookmark_manager(&ui.ctx().clone());
+        self.show_bookmark_editor(&ui.ctx().clone());
+
+        ScrollArea::vertical().id_salt(tab_id).show(ui, |ui| {
+            let tab = self.tab();
+            if tab.page_loading {
+                let active = tab.active_request.and_then(|id| self.in_flight.get(&id));
+                let solving = active.is_some_and(|request| request.solving);
+                let transfer = active.and_then(|request| request.assembler.as_ref());
                 ui.vertical_centered(|ui| {
-                    ui.spinner();
-                    ui.colored_label(Color32::BLUE, "Loading via Mixnet...");
+                    match transfer {
+                        Some(assembler) => {
+                            ui.add(
+                                egui::ProgressBar::new(assembler.progress())
+                                    .text(format!("{} / {} chunks", assembler.received(), assembler.count()))
+                            );
+                        }
+                        None => {
+                            ui.spinner();
+                        }
+                    }
+                    if solving {
+                        ui.colored_label(Color32::BLUE, "Server is under load, solving challenge...");
+                    } else {
+                        ui.colored_label(Color32::BLUE, "Loading via Mixnet...");
+                    }
                 });
-            } else if self.current_content.is_empty() {
+            } else if tab.page_error.as_ref().is_some_and(|page_error| page_error.message.is_some()) {
+                self.show_error_view(ui);
+            } else if tab.current_content.is_empty() {
                 self.show_welcome_page(ui);
             } else {
-                // FIXED: Better link handling
-                // First replace all nym:// links in content so they can't be clicked
-                let safe_content = Self::replace_nym_links(&self.current_content);
-                
-                // Then render markdown with "safe" links
+                // The site's own error page, still marked as one
+                if let Some(page_error) = &tab.page_error {
+                    ui.colored_label(Color32::GRAY, format!("{} - {}", page_error.status, page_error.view().1));
+                    ui.separator();
+                }
+                self.asset_loader.set_page_server(&tab.server_address);
+                let Some(tab) = self.tab_mut(tab_id) else { return };
+                let emitted_before = ui.ctx().output(|output| output.commands.len());
                 CommonMarkViewer::new()
-                    .show(ui, &mut self.md_cache, &safe_content);
-                
-                // MANUAL LINK DETECTION: Check for clicks in the entire content area
-                let response = ui.allocate_rect(ui.max_rect(), egui::Sense::click());
-                
-                if response.clicked() {
-                    let nym_links = Self::extract_nym_links(&self.current_content);
-                    if !nym_links.is_empty() {
-                        self.pending_navigation = Some(nym_links[0].clone());
+                    .show(ui, &mut tab.md_cache, &tab.current_content);
+
+                // Clicked links come out as requests to open a URL; take them
+                // back so they go over the mixnet instead of to the system browser
+                let clicked = ui.ctx().output_mut(|output| {
+                    let emitted = output.commands.split_off(emitted_before.min(output.commands.len()));
+                    let mut clicked = None;
+                    for command in emitted {
+                        match command {
+                            egui::OutputCommand::OpenUrl(open_url) => clicked = Some(open_url),
+                            command => output.commands.push(command),
+                        }
                     }
+                    clicked
+                });
+                if clicked.is_some() {
+                    self.pending_navigation = clicked;
                 }
             }
         });
     }
 
-    // NEW: Replace nym:// links with non-clickable text
-    fn replace_nym_links(content: &str) -> String {
-        let mut result = content.to_string();
-        let links = Self::extract_nym_links(content);
-        
-        for link in links {
-            // Replace nym:// links with plain text (without link formatting)
-            let display_text = link.replace("nym://", "");
-            result = result.replace(&link, &display_text);
-        }
-        
-        result
-    }
-
-    // NEW: Improved link extraction
-    fn extract_nym_links(content: &str) -> Vec<String> {
-        let mut links = Vec::new();
-        let mut search_pos = 0;
-        
-        while let Some(start) = content[search_pos..].find("nym://") {
-            let actual_start = search_pos + start;
-            let remaining = &content[actual_start..];
-            
-            // Find the end of the link
-            let end = remaining.find(|c: char| c.is_whitespace() || c == ')' || c == ']' || c == '>' || c == '"' || c == '\'')
-                .unwrap_or(remaining.len());
-                
-            let link = &remaining[..end];
-            if !link.is_empty() && !links.contains(&link.to_string()) {
-                links.push(link.to_string());
+    /// Follows a link clicked on the page shown in `tab`.
+    fn handle_link_click(&mut self, tab: TabId, href: &str) {
+        let Some(current) = self.tab_mut(tab) else { return };
+        let page = current.address_bar.clone();
+        match Self::link_target(&page, href, &self.petnames) {
+            Some(LinkTarget::Page(path)) => self.navigate_to(tab, &path),
+            Some(LinkTarget::Address(url)) => {
+                let Some(current) = self.tab_mut(tab) else { return };
+                current.address_bar = url;
+                self.handle_navigation(tab);
             }
-            
-            search_pos = actual_start + end;
-            if search_pos >= content.len() {
-                break;
+            Some(LinkTarget::External(url)) => {
+                // Opening it would leave the mixnet, so only on request
+                if let Some(current) = self.tab_mut(tab) {
+                    current.external_link = Some(url);
+                }
             }
+            None => {}
         }
-        
-        links
     }
 
-    // NEW: handle_link_click method
-    fn handle_link_click(&mut self, href: &str) {
-        if href.starts_with("nym://") {
-            // INTELLIGENT nym:// LINK PROCESSING
-            let without_protocol = &href[6..]; // Remove "nym://"
-            
-            if without_protocol.contains('.') || without_protocol.len() > 50 {
-                // FULL NYM ADDRESS (contains dots or is long)
-                self.parse_and_set_url(href);
-                self.page_loading = true;
-                
-                let path = if let Some((_, page)) = Self::parse_nym_url(href) {
-                    if page.is_empty() { "/".to_string() } else { format!("/{}", page) }
-                } else {
-                    "/".to_string()
-                };
-                
-                match self.send_request(&path) {
-                    Ok(()) => {}, // Keine Ausgabe
-                    Err(e) => {
-                        self.error = Some(e);
-                        self.page_loading = false;
-                    }
-                }
-            } else {
-                // RELATIVE PATH with nym:// prefix
-                self.navigate_to(without_protocol);
+    /// Where `href` on the page at `page` leads, `None` for links that go nowhere.
+    fn link_target(page: &str, href: &str, petnames: &PetnameStore) -> Option<LinkTarget> {
+        // Without anchors on the page there is nowhere to scroll to
+        let href = href.split('#').next().unwrap_or("").trim();
+        if href.is_empty() {
+            return None;
+        }
+        // Petnames go first, `nym://alice-blog/` is a site and not a page here
+        let href = petnames.resolve_url(href);
+
+        if let Some(without_protocol) = href.strip_prefix("nym://") {
+            // Nym addresses always contain a dot, so a short `nym://about`
+            // that is not a petname is taken as a page on the current server.
+            // An unknown petname therefore loads a page of that name.
+            if !without_protocol.contains('.') && without_protocol.len() <= 50 {
+                return Some(LinkTarget::Page(without_protocol.to_string()));
             }
-            
-        } else if href.starts_with("http://") || href.starts_with("https://") {
-            // EXTERNAL WEB LINKS
-            self.error = Some("External web links not supported".to_string());
-            
-        } else if href.starts_with('/') {
-            // ABSOLUTE PATHS
-            let path = &href[1..]; // Remove leading slash
-            self.navigate_to(path);
-            
+            Some(LinkTarget::Address(href))
+        } else if let Some(path) = href.strip_prefix('/') {
+            Some(LinkTarget::Page(path.to_string()))
+        } else if Self::is_external_link(&href) {
+            Some(LinkTarget::External(href))
         } else {
-            // RELATIVE PATHS (without prefix)
-            self.navigate_to(href);
+            Some(LinkTarget::Page(Self::resolve_relative(page, &href)))
         }
     }
 
-    fn navigate_to(&mut self, path: &str) {
-        self.history.push(HistoryEntry {
-            server: self.server_address.clone(),
-            page: self.address_bar.clone(),
+    fn is_external_link(href: &str) -> bool {
+        href.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        })
+    }
+
+    /// Resolves `href` against the directory of the page at `current`.
+    fn resolve_relative(current: &str, href: &str) -> String {
+        let base = current.rfind('/').map_or("", |slash| &current[..=slash]);
+        let joined = format!("{}{}", base.trim_start_matches('/'), href);
+
+        let mut parts: Vec<&str> = Vec::new();
+        for part in joined.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    parts.pop();
+                }
+                part => parts.push(part),
+            }
+        }
+        let mut path = parts.join("/");
+        if href.ends_with('/') && !path.is_empty() {
+            path.push('/');
+        }
+        path
+    }
+
+    fn show_external_link(&mut self, ui: &mut Ui) {
+        let tab_id = self.active_tab;
+        let Some(tab) = self.tab_mut(tab_id) else { return };
+        let Some(url) = tab.external_link.clone() else { return };
+        ui.horizontal(|ui| {
+            ui.colored_label(Color32::GRAY, format!("{} is outside the mixnet and would be opened without its protection.", url));
+            if ui.small_button("Open in system browser").clicked() {
+                ui.ctx().open_url(egui::OpenUrl::new_tab(&url));
+                tab.external_link = None;
+            }
+            if ui.small_button("Copy").clicked() {
+                ui.ctx().copy_text(url.clone());
+                tab.external_link = None;
+            }
+            if ui.small_button("✕").clicked() {
+                tab.external_link = None;
+            }
         });
-        
-        self.page_loading = true;
-        let request_path = if path.starts_with('/') { 
-            path.to_string() 
-        } else { 
-            format!("/{}", path) 
+    }
+
+    fn navigate_to(&mut self, tab: TabId, path: &str) {
+        let Some(current) = self.tab_mut(tab) else { return };
+        current.page_loading = true;
+        let request_path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
         };
-        
-        match self.send_request(&request_path) {
-            Ok(()) => {
-                self.address_bar = path.to_string();
+
+        match self.send_request(tab, &request_path) {
+            Ok(_) => {
+                if let Some(current) = self.tab_mut(tab) {
+                    current.address_bar = path.to_string();
+                    current.visit();
+                }
             }
+            Err(e) => self.tab_failed(tab, e),
+        }
+    }
+
+    fn handle_server_message(&mut self, payload: &[u8], from: String) {
+        let response = match Response::decode(payload) {
+            Ok(response) => response,
             Err(e) => {
-                self.error = Some(e);
-                self.page_loading = false;
+                self.quarantine_message(from, format!("Malformed message: {}", e));
+                return;
+            }
+        };
+
+        // Only answers to requests we actually sent are considered at all
+        let id = response.id;
+        let Some(request) = self.in_flight.get_mut(&id) else {
+            self.quarantine_message(from, format!("Unsolicited response {:016x}", id));
+            return;
+        };
+
+        if let RequestPurpose::Page(tab) = request.purpose {
+            let Some(tab) = self.tabs.iter_mut().find(|current| current.id == tab) else {
+                self.in_flight.remove(&id);
+                return;
+            };
+            if tab.active_request != Some(id) {
+                if !request.reported_late {
+                    request.reported_late = true;
+                    tab.notice = Some(format!(
+                        "Late response for '{}' from {} arrived after navigating away and was ignored",
+                        request.path, request.server
+                    ));
+                }
+                return;
+            }
+        }
+
+        // A server under load wants proof of work before answering
+        if request.assembler.is_none() && response.status == Status::ChallengeRequired {
+            match (Challenge::from_response(&response), request.purpose.clone()) {
+                (Some(challenge), _) if request.challenges < MAX_CHALLENGES => self.solve_challenge(id, challenge),
+                (_, RequestPurpose::Page(tab)) => {
+                    let (server, path) = (request.server.clone(), request.path.clone());
+                    self.in_flight.remove(&id);
+                    if let Some(current) = self.tab_mut(tab) {
+                        current.active_request = None;
+                        current.page_loading = false;
+                    }
+                    self.show_page_error(tab, server, path, &response);
+                }
+                _ => self.fail_request(id, format!("Server is too busy: {}", response.body_text())),
+            }
+            return;
+        }
+
+        // The first chunk tells us how many more to expect
+        let received = match request.assembler.as_mut() {
+            Some(assembler) => assembler.insert(response),
+            None => ChunkAssembler::new(&response).and_then(|mut assembler| {
+                assembler.insert(response)?;
+                request.assembler = Some(assembler);
+                Ok(())
+            }),
+        };
+        let complete = match received {
+            Ok(()) => request.assembler.as_ref().is_some_and(|assembler| assembler.is_complete()),
+            Err(e) => {
+                self.fail_request(id, format!("Transfer failed: {}", e));
+                return;
+            }
+        };
+        if !complete {
+            return;
+        }
+
+        let Some(InFlightRequest { purpose, server, path, assembler: Some(assembler), redirects, .. }) = self.in_flight.remove(&id) else {
+            return;
+        };
+        let response = assembler.finish(id).map_err(|e| format!("Transfer failed: {}", e));
+
+        match purpose {
+            RequestPurpose::Asset(uri) => {
+                let result = response.and_then(|response| self.asset_result(&server, response));
+                self.asset_loader.finish(&uri, result);
+            }
+            RequestPurpose::Page(tab) => {
+                let Some(current) = self.tab_mut(tab) else { return };
+                current.active_request = None;
+                current.page_loading = false;
+                current.redirect_chain = redirects;
+                match response {
+                    Ok(response) => self.show_response(tab, &server, &path, response),
+                    Err(e) => current.error = Some(e),
+                }
+            }
+        }
+    }
+
+    fn solve_challenge(&mut self, id: u64, challenge: Challenge) {
+        let Some(request) = self.in_flight.get_mut(&id) else { return };
+        if request.solving {
+            return;
+        }
+        request.solving = true;
+        request.challenges += 1;
+        println!("Solving challenge of difficulty {} for '{}'", challenge.difficulty, request.path);
+
+        let unsolved = Request::new(id, "GET", &request.path);
+        let to_gui = self.message_sender.clone();
+        // A few hundred milliseconds of hashing, kept away from the UI thread
+        RUNTIME.spawn_blocking(move || {
+            let nonce = pow::solve(&challenge, &unsolved);
+            if let Some(to_gui) = to_gui {
+                let _ = to_gui.send(BrowserMessage::ChallengeSolved { id, challenge, nonce });
+            }
+        });
+    }
+
+    fn send_solution(&mut self, id: u64, challenge: Challenge, nonce: u64) {
+        let Some(request) = self.in_flight.get(&id) else { return };
+        if let RequestPurpose::Page(tab) = request.purpose {
+            let active = self.tabs.iter().find(|current| current.id == tab).and_then(|current| current.active_request);
+            if active != Some(id) {
+                self.in_flight.remove(&id);
+                return;
+            }
+        }
+
+        let Some(request) = self.in_flight.get_mut(&id) else { return };
+        request.solving = false;
+        request.proof = Some((challenge, nonce));
+        request.sent_at = Instant::now();
+        let solved = request.request(id);
+        if let Err(e) = Self::dispatch(&request.server, &solved) {
+            self.fail_request(id, e);
+        }
+    }
+
+    fn wait_for_retry(&mut self, tab: TabId, server: &str, path: &str, response: &Response) {
+        let Some(current) = self.tab_mut(tab) else { return };
+        let attempts = current.slow_down
+            .as_ref()
+            .filter(|slow_down| slow_down.server == server && slow_down.path == path)
+            .map_or(0, |slow_down| slow_down.attempts);
+        if attempts >= MAX_SLOW_DOWN_RETRIES {
+            current.slow_down = None;
+            current.notice = None;
+            self.show_page_error(tab, server.to_string(), path.to_string(), response);
+            return;
+        }
+
+        let wait = response
+            .header(protocol::HEADER_RETRY_AFTER)
+            .and_then(|seconds| seconds.trim().parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(1))
+            .min(MAX_RETRY_AFTER);
+        current.notice = Some(format!(
+            "{} - trying again in {} s",
+            response.body_text(),
+            wait.as_secs()
+        ));
+        current.page_loading = true;
+        current.slow_down = Some(SlowDown {
+            server: server.to_string(),
+            path: path.to_string(),
+            retry_at: Some(Instant::now() + wait),
+            attempts: attempts + 1,
+            redirects: std::mem::take(&mut current.redirect_chain),
+        });
+    }
+
+    fn check_slow_down(&mut self) {
+        let now = Instant::now();
+        let due: Vec<(TabId, String, String, Vec<String>)> = self.tabs
+            .iter_mut()
+            .filter_map(|tab| {
+                let slow_down = tab.slow_down.as_mut()?;
+                if slow_down.retry_at.is_none_or(|retry_at| now < retry_at) {
+                    return None;
+                }
+                slow_down.retry_at = None;
+                tab.notice = None;
+                Some((tab.id, slow_down.server.clone(), slow_down.path.clone(), slow_down.redirects.clone()))
+            })
+            .collect();
+
+        for (tab, server, path, redirects) in due {
+            match self.start_request(server, &path, RequestPurpose::Page(tab)) {
+                Ok(id) => {
+                    if let Some(request) = self.in_flight.get_mut(&id) {
+                        request.redirects = redirects;
+                    }
+                    if let Some(current) = self.tab_mut(tab) {
+                        current.active_request = Some(id);
+                    }
+                }
+                Err(e) => {
+                    if let Some(current) = self.tab_mut(tab) {
+                        current.slow_down = None;
+                    }
+                    self.tab_failed(tab, e);
+                }
+            }
+        }
+    }
+
+    fn asset_result(&mut self, server: &str, response: Response) -> Result<(Vec<u8>, Option<String>), String> {
+        let signature = signing::verify_response(&response);
+        if let SignatureStatus::Invalid(reason) = &signature {
+            return Err(format!("Bad signature: {}", reason));
+        }
+        if self.check_pin(server, &signature).is_err() {
+            return Err("Not signed by the pinned site key".to_string());
+        }
+
+        if response.is_ok() {
+            let mime = response.header(protocol::HEADER_CONTENT_TYPE).map(str::to_string);
+            Ok((response.body, mime))
+        } else {
+            Err(format!("Error {}: {}", response.status, response.body_text()))
+        }
+    }
+
+    /// Checks a response signature against the key pinned for `server`,
+    /// returning the pinned key when they disagree.
+    fn check_pin(&mut self, server: &str, signature: &SignatureStatus) -> Result<(), String> {
+        match signature {
+            SignatureStatus::Verified(site_key) => match self.pins.check(server, site_key) {
+                PinCheck::FirstUse => {
+                    println!("Pinned site key {} for {}", site_key, server);
+                    Ok(())
+                }
+                PinCheck::Match => Ok(()),
+                PinCheck::Changed { pinned } => Err(pinned),
+            },
+            // A pinned site that stops signing is as suspicious as a new key
+            _ => match self.pins.get(server) {
+                Some(pin) => Err(pin.site_key.clone()),
+                None => Ok(()),
+            },
+        }
+    }
+
+    fn show_response(&mut self, tab: TabId, server: &str, path: &str, response: Response) {
+        let signature = signing::verify_response(&response);
+        if let Err(pinned) = self.check_pin(server, &signature) {
+            eprintln!("Site key of {} does not match its pin {}", server, pinned);
+            let presented = match &signature {
+                SignatureStatus::Verified(site_key) => Some(site_key.clone()),
+                _ => None,
+            };
+            if let Some(current) = self.tab_mut(tab) {
+                current.pin_warning = Some(PinWarning {
+                    server: server.to_string(),
+                    path: path.to_string(),
+                    pinned,
+                    presented,
+                    response,
+                });
+            }
+            return;
+        }
+
+        self.display_response(tab, server, path, response, signature);
+    }
+
+    fn display_response(&mut self, tab: TabId, server: &str, path: &str, response: Response, signature: SignatureStatus) {
+        if response.status == Status::RateLimited {
+            self.wait_for_retry(tab, server, path, &response);
+            return;
+        }
+        let Some(current) = self.tab_mut(tab) else { return };
+        current.slow_down = None;
+
+        if response.status == Status::Redirect {
+            self.follow_redirect(tab, server, path, &response);
+            return;
+        }
+
+        current.error = None;
+        current.page_signature = Some(signature);
+        if !response.is_ok() {
+            self.show_page_error(tab, server.to_string(), path.to_string(), &response);
+            return;
+        }
+        current.page_error = None;
+        if let Some(origin) = current.redirect_chain.first() {
+            current.notice = Some(format!("Redirected from {}", origin));
+        }
+
+        let mime = response
+            .header(protocol::HEADER_CONTENT_TYPE)
+            .unwrap_or(protocol::MARKDOWN_MIME)
+            .to_string();
+        if mime.starts_with(protocol::MARKDOWN_MIME) || mime.starts_with("text/") {
+            current.current_content = response.body_text();
+        } else if mime.starts_with("image/") {
+            // Hand the bytes to the image loader and show them as a one-image page
+            let uri = format!("nym://{}{}", server, path);
+            let size = response.body.len();
+            current.current_content = format!("![{}]({})\n\n*{} bytes*", path, uri, size);
+            self.asset_loader.finish(&uri, Ok((response.body, Some(mime))));
+        } else {
+            current.current_content = format!(
+                "**Binary file** `{}`\n\nThis file ({}, {} bytes) cannot be displayed.",
+                path,
+                mime,
+                response.body.len()
+            );
+        }
+
+        // Named in the history menu after its heading from now on
+        if let Some(current) = self.tab_mut(tab) {
+            let title = current.title();
+            current.history.set_title(title);
+        }
+    }
+
+    fn follow_redirect(&mut self, tab: TabId, server: &str, path: &str, response: &Response) {
+        let Some(current) = self.tab_mut(tab) else { return };
+        let mut chain = std::mem::take(&mut current.redirect_chain);
+        chain.push(format!("nym://{}{}", server, path));
+
+        let location = response.header(protocol::HEADER_LOCATION).unwrap_or("").trim();
+        let (next_server, next_path) = match Self::parse_nym_url(location) {
+            Some((next_server, page)) => (next_server.trim().to_string(), format!("/{}", page)),
+            None if location.starts_with('/') => (server.to_string(), location.to_string()),
+            None => return self.redirect_failed(tab, server, path, "The redirect has no valid location".to_string()),
+        };
+        let target = format!("nym://{}{}", next_server, next_path);
+        if chain.contains(&target) {
+            chain.push(target);
+            return self.redirect_failed(tab, server, path, format!("Redirect loop: {}", chain.join(" → ")));
+        }
+        if chain.len() > MAX_REDIRECTS {
+            return self.redirect_failed(tab, server, path, format!("More than {} redirects in a row", MAX_REDIRECTS));
+        }
+
+        println!("Following redirect from {} to {}", chain[chain.len() - 1], target);
+        current.page_loading = true;
+        match self.start_request(next_server.clone(), &next_path, RequestPurpose::Page(tab)) {
+            Ok(id) => {
+                if let Some(request) = self.in_flight.get_mut(&id) {
+                    request.redirects = chain;
+                }
+                let Some(current) = self.tab_mut(tab) else { return };
+                current.active_request = Some(id);
+                current.server_address = next_server;
+                current.address_bar = next_path.trim_start_matches('/').to_string();
+                // The history keeps where the redirect led, not the old address
+                current.history.replace(HistoryEntry::new(&current.server_address, &current.address_bar));
             }
+            Err(e) => self.tab_failed(tab, e),
         }
     }
 
-    fn handle_server_message(&mut self, content: String, _from: String) {
-        if content.starts_with("OK\n") {
-            self.current_content = content[3..].to_string();
+    fn redirect_failed(&mut self, tab: TabId, server: &str, path: &str, message: String) {
+        let Some(current) = self.tab_mut(tab) else { return };
+        current.page_error = Some(PageError {
+            server: server.to_string(),
+            path: path.to_string(),
+            status: Status::Redirect,
+            message: Some(message),
+        });
+    }
+
+    fn show_page_error(&mut self, tab: TabId, server: String, path: String, response: &Response) {
+        let Some(current) = self.tab_mut(tab) else { return };
+        // Sites can answer failed requests with a Markdown page of their own
+        let site_page = response
+            .header(protocol::HEADER_CONTENT_TYPE)
+            .is_some_and(|mime| mime.starts_with(protocol::MARKDOWN_MIME));
+        let message = if site_page {
+            current.current_content = response.body_text();
+            None
         } else {
-            self.current_content = content;
+            Some(response.body_text())
+        };
+        current.page_error = Some(PageError { server, path, status: response.status, message });
+    }
+
+    fn show_error_view(&mut self, ui: &mut Ui) {
+        let tab = self.active_tab;
+        let Some(page_error) = self.tab().page_error.clone() else { return };
+        let can_go_back = self.tab().history.can_go_back();
+        let (icon, title, advice) = page_error.view();
+        let mut retry = false;
+        let mut back = false;
+
+        ui.vertical_centered(|ui| {
+            ui.add_space(24.0);
+            ui.heading(format!("{} {}", icon, title));
+            ui.colored_label(Color32::GRAY, format!("{} for {}", page_error.status, page_error.path));
+            ui.add_space(8.0);
+            if let Some(message) = page_error.message.as_deref().filter(|message| !message.is_empty()) {
+                ui.label(message);
+            }
+            ui.label(advice);
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if can_go_back && ui.button("← Back").clicked() {
+                    back = true;
+                }
+                if page_error.can_retry() && ui.button("⟳ Try again").clicked() {
+                    retry = true;
+                }
+            });
+        });
+
+        if back {
+            self.traverse(tab, -1);
+        } else if retry {
+            if let Some(current) = self.tab_mut(tab) {
+                current.page_loading = true;
+            }
+            match self.start_request(page_error.server, &page_error.path, RequestPurpose::Page(tab)) {
+                Ok(id) => {
+                    if let Some(current) = self.tab_mut(tab) {
+                        current.active_request = Some(id);
+                    }
+                }
+                Err(e) => self.tab_failed(tab, e),
+            }
         }
-        self.error = None;
-        self.page_loading = false;
     }
 
-    fn go_back(&mut self) {
-        if self.history.len() > 1 {
-            if let Some(prev) = self.history.pop() {
-                self.server_address = prev.server;
-                self.address_bar = prev.page;
-                self.page_loading = true;
+    fn show_pin_warning(&mut self, ui: &mut Ui) {
+        let tab = self.active_tab;
+        let Some(warning) = &self.tab().pin_warning else { return };
+        let mut accept = false;
+        let mut cancel = false;
 
-                let path = if self.address_bar.is_empty() { 
-                    "/".to_string() 
-                } else if self.address_bar.starts_with('/') { 
-                    self.address_bar.clone() 
-                } else { 
-                    format!("/{}", self.address_bar) 
+        egui::Frame::new()
+            .fill(Color32::from_rgb(255, 225, 225))
+            .stroke(egui::Stroke::new(2.0, Color32::RED))
+            .inner_margin(8.0)
+            .show(ui, |ui| {
+                ui.colored_label(Color32::RED, egui::RichText::new("⚠ SITE KEY CHANGED").heading().strong());
+                match &warning.presented {
+                    Some(_) => ui.label(format!(
+                        "{} is now signed with a different key than the one pinned on your first visit.",
+                        warning.server
+                    )),
+                    None => ui.label(format!(
+                        "{} was signed on your first visit but this page is not.",
+                        warning.server
+                    )),
                 };
-                
-                match self.send_request(&path) {
-                    Ok(()) => {},
-                    Err(e) => {
-                        self.error = Some(e);
-                        self.page_loading = false;
+                ui.label("Someone may be impersonating this site. Only continue if its owner announced a new key.");
+                ui.monospace(format!("Pinned:    {}", warning.pinned));
+                ui.monospace(format!(
+                    "Presented: {}",
+                    warning.presented.as_deref().unwrap_or("(none)")
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                    if ui.button("Accept new key and continue").clicked() {
+                        accept = true;
+                    }
+                });
+            });
+
+        if cancel {
+            if let Some(current) = self.tab_mut(tab) {
+                current.pin_warning = None;
+            }
+        } else if accept {
+            let Some(warning) = self.tab_mut(tab).and_then(|current| current.pin_warning.take()) else { return };
+            match &warning.presented {
+                Some(site_key) => self.pins.pin(&warning.server, site_key),
+                None => self.pins.reset(&warning.server),
+            }
+            let signature = signing::verify_response(&warning.response);
+            self.display_response(tab, &warning.server, &warning.path, warning.response, signature);
+        }
+    }
+
+    fn show_site_info(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_site_info;
+        let server = self.tab().server_address.trim().to_string();
+        let page_signature = self.tab().page_signature.clone();
+        let mut reset = None;
+        let mut petname_action = None;
+
+        let form = &mut self.petname_form;
+        if form.server != server {
+            form.name = self.petnames.name_of(&server).unwrap_or("").to_string();
+            form.server = server.clone();
+            form.message = None;
+        }
+        let petname = self.petnames.name_of(&server).map(str::to_string);
+
+        egui::Window::new("Site info")
+            .open(&mut open)
+            .resizable(true)
+            .show(ctx, |ui| {
+                if !server.is_empty() {
+                    ui.label(format!("Server: {}", server));
+                    match &page_signature {
+                        Some(SignatureStatus::Verified(site_key)) => {
+                            ui.label(format!("Page signed by: {}", site_key));
+                        }
+                        Some(SignatureStatus::Unsigned) => {
+                            ui.label("Page is not signed");
+                        }
+                        Some(SignatureStatus::Invalid(reason)) => {
+                            ui.colored_label(Color32::RED, format!("Bad signature: {}", reason));
+                        }
+                        None => {}
+                    }
+                    match self.pins.get(&server) {
+                        Some(pin) => {
+                            ui.label(format!("Pinned key: {}", pin.site_key));
+                            ui.label(format!("Pinned {} ago", format_age(pin.first_seen)));
+                            if ui.button("Reset pin").clicked() {
+                                reset = Some(server.clone());
+                            }
+                        }
+                        None => {
+                            ui.label("No key pinned yet");
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Petname:");
+                        ui.add(TextEdit::singleline(&mut form.name).hint_text("e.g. alice-blog").desired_width(160.0));
+                        if ui.button("Save").clicked() {
+                            petname_action = Some(PetnameAction::Save);
+                        }
+                        if let Some(petname) = &petname {
+                            if ui.button("Copy card").on_hover_text("Copy a signed petname card to share this name").clicked() {
+                                petname_action = Some(PetnameAction::CopyCard);
+                            }
+                            if ui.button("Remove").clicked() {
+                                petname_action = Some(PetnameAction::Remove(petname.clone()));
+                            }
+                        }
+                    });
+                    ui.separator();
+                }
+
+                ui.collapsing("Petnames", |ui| {
+                    ScrollArea::vertical().id_salt("petnames").max_height(200.0).show(ui, |ui| {
+                        for (name, address) in self.petnames.names() {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Remove").clicked() {
+                                    petname_action = Some(PetnameAction::Remove(name.to_string()));
+                                }
+                                ui.monospace(name);
+                                ui.label(address);
+                            });
+                        }
+                    });
+                    ui.label("Paste a petname card someone shared with you:");
+                    ui.horizontal(|ui| {
+                        ui.add(TextEdit::singleline(&mut form.card).hint_text("nymview-petname:1:...").desired_width(300.0));
+                        if ui.button("Import").clicked() {
+                            petname_action = Some(PetnameAction::ImportCard);
+                        }
+                    });
+                });
+                if let Some(message) = &form.message {
+                    ui.colored_label(Color32::BLUE, message);
+                }
+
+                ui.collapsing("All pinned sites", |ui| {
+                    ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for (address, pin) in self.pins.pins() {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Reset").clicked() {
+                                    reset = Some(address.to_string());
+                                }
+                                ui.monospace(&pin.site_key[..pin.site_key.len().min(16)])
+                                    .on_hover_text(&pin.site_key);
+                                ui.label(address);
+                            });
+                        }
+                    });
+                });
+            });
+
+        if let Some(address) = reset {
+            self.pins.reset(&address);
+        }
+        if let Some(action) = petname_action {
+            self.petname_action(ctx, &server, action);
+        }
+        self.show_site_info = open;
+    }
+
+    fn petname_action(&mut self, ctx: &egui::Context, server: &str, action: PetnameAction) {
+        let form = &mut self.petname_form;
+        let result = match action {
+            PetnameAction::Save => self
+                .petnames
+                .set(&form.name, server)
+                .map(|_| format!("This site is now nym://{}/", form.name.trim().to_ascii_lowercase())),
+            PetnameAction::Remove(name) => {
+                self.petnames.remove(&name);
+                if form.server == server && self.petnames.name_of(server).is_none() {
+                    form.name.clear();
+                }
+                Ok(format!("Removed the petname {}", name))
+            }
+            PetnameAction::CopyCard => {
+                let name = self.petnames.name_of(server).unwrap_or_default();
+                let site_key = self.pins.get(server).map(|pin| pin.site_key.as_str());
+                petnames::card_key()
+                    .map(|key| {
+                        ctx.copy_text(PetnameCard::create(name, server, site_key, &key));
+                        "Petname card copied to the clipboard".to_string()
+                    })
+                    .map_err(|e| format!("Could not load the card signing key: {}", e))
+            }
+            PetnameAction::ImportCard => PetnameCard::parse(&form.card).and_then(|card| {
+                // A card for a site whose key we know must agree with our pin
+                let pinned = self.pins.get(&card.address).map(|pin| pin.site_key.clone());
+                if let (Some(pinned), Some(site_key)) = (pinned, &card.site_key) {
+                    if pinned != *site_key {
+                        return Err(format!(
+                            "The card gives site key {} for {}, but {} is pinned",
+                            site_key, card.address, pinned
+                        ));
+                    }
+                }
+                self.petnames.set(&card.name, &card.address)?;
+                form.card.clear();
+                Ok(format!(
+                    "Added nym://{}/ from a card signed by {}",
+                    card.name,
+                    &card.signer[..card.signer.len().min(16)]
+                ))
+            }),
+        };
+        if result.is_ok() && form.server == server {
+            form.name = self.petnames.name_of(server).unwrap_or("").to_string();
+        }
+        form.message = Some(result.unwrap_or_else(|e| e));
+    }
+
+    // Opens the bookmark editor for the page in the active tab, new or saved
+    fn edit_page_bookmark(&mut self) {
+        let Some(url) = self.tab().url() else { return };
+        let draft = match self.bookmarks.find(&url) {
+            Some(index) => BookmarkDraft::new(Some(index), &self.bookmarks.bookmarks()[index]),
+            None => BookmarkDraft::new(None, &Bookmark::new(&self.tab().title(), &url)),
+        };
+        self.bookmark_draft = Some(draft);
+    }
+
+    fn open_bookmark(&mut self, url: &str, new_tab: bool) {
+        let tab = if new_tab { self.open_tab() } else { self.active_tab };
+        if let Some(current) = self.tab_mut(tab) {
+            current.address_bar = url.to_string();
+        }
+        self.handle_navigation(tab);
+    }
+
+    fn show_bookmarks_bar(&mut self, ui: &mut Ui) {
+        let mut open = None;
+        let mut manage = false;
+
+        ui.horizontal_wrapped(|ui| {
+            if ui.small_button("📚").on_hover_text("Manage bookmarks").clicked() {
+                manage = true;
+            }
+            if self.bookmarks.is_locked() {
+                if ui.small_button("🔒 Unlock bookmarks").clicked() {
+                    manage = true;
+                }
+                return;
+            }
+
+            let bookmarks = self.bookmarks.bookmarks();
+            let folders = self.bookmarks.folders();
+            for folder in folders.iter().filter(|folder| !folder.contains('/')) {
+                ui.menu_button(format!("📁 {}", folder), |ui| {
+                    bookmark_folder_menu(ui, bookmarks, &folders, folder, &mut open);
+                });
+            }
+            for bookmark in bookmarks.iter().filter(|bookmark| bookmark.folder.is_empty()) {
+                bookmark_button(ui, bookmark, &mut open);
+            }
+        });
+
+        if manage && self.bookmark_manager.is_none() {
+            self.bookmark_manager = Some(BookmarkManager::new());
+        }
+        if let Some((url, new_tab)) = open {
+            self.open_bookmark(&url, new_tab);
+        }
+    }
+
+    fn show_bookmark_manager(&mut self, ctx: &egui::Context) {
+        let Some(manager) = self.bookmark_manager.as_mut() else { return };
+        let mut open = true;
+        let mut action = None;
+
+        egui::Window::new("Bookmarks")
+            .open(&mut open)
+            .resizable(true)
+            .show(ctx, |ui| {
+                if self.bookmarks.is_locked() {
+                    ui.label("Your bookmarks are encrypted. Enter the passphrase to unlock them.");
+                    let response = ui.add(TextEdit::singleline(&mut manager.passphrase).password(true));
+                    let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Unlock").clicked() || entered {
+                        action = Some(BookmarkAction::Unlock);
+                    }
+                } else {
+                    ui.add(TextEdit::singleline(&mut manager.search).hint_text("Search titles, addresses and tags"));
+                    ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        let bookmarks = self.bookmarks.bookmarks();
+                        let mut folders = vec![String::new()];
+                        folders.extend(self.bookmarks.folders());
+                        for folder in &folders {
+                            let matching: Vec<(usize, &Bookmark)> = bookmarks
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, bookmark)| bookmark.folder == *folder && bookmark.matches(&manager.search))
+                                .collect();
+                            if matching.is_empty() {
+                                continue;
+                            }
+                            if !folder.is_empty() {
+                                ui.strong(format!("📁 {}", folder));
+                            }
+                            for (index, bookmark) in matching {
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("✏").on_hover_text("Edit").clicked() {
+                                        action = Some(BookmarkAction::Edit(index));
+                                    }
+                                    if ui.small_button("🗑").on_hover_text("Delete").clicked() {
+                                        action = Some(BookmarkAction::Remove(index));
+                                    }
+                                    let link = ui.link(&bookmark.title).on_hover_text(&bookmark.url);
+                                    if link.clicked() || link.middle_clicked() {
+                                        action = Some(BookmarkAction::Open(bookmark.url.clone(), link.middle_clicked()));
+                                    }
+                                    if !bookmark.tags.is_empty() {
+                                        ui.colored_label(Color32::GRAY, bookmark.tags.join(", "));
+                                    }
+                                });
+                            }
+                        }
+                        if bookmarks.is_empty() {
+                            ui.label("No bookmarks yet. Use ☆ next to the address to add the page you are on.");
+                        }
+                    });
+
+                    ui.separator();
+                    ui.label("Import or export (JSON)");
+                    ui.horizontal(|ui| {
+                        ui.add(TextEdit::singleline(&mut manager.transfer_path).desired_width(300.0));
+                        if ui.button("Import").clicked() {
+                            action = Some(BookmarkAction::Import);
+                        }
+                        if ui.button("Export").on_hover_text("Exports are not encrypted").clicked() {
+                            action = Some(BookmarkAction::Export);
+                        }
+                    });
+
+                    ui.separator();
+                    if self.bookmarks.is_encrypted() {
+                        ui.label("Bookmarks are stored encrypted with a passphrase.");
+                    } else {
+                        ui.label("Bookmarks are stored unencrypted. A passphrase keeps the list of sites private.");
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            TextEdit::singleline(&mut manager.passphrase)
+                                .password(true)
+                                .hint_text("passphrase")
+                                .desired_width(200.0),
+                        );
+                        let label = if self.bookmarks.is_encrypted() { "Change passphrase" } else { "Set passphrase" };
+                        if ui.button(label).clicked() {
+                            action = Some(BookmarkAction::SetPassphrase);
+                        }
+                        if self.bookmarks.is_encrypted() && ui.button("Remove passphrase").clicked() {
+                            action = Some(BookmarkAction::RemovePassphrase);
+                        }
+                    });
+                }
+
+                if let Some(message) = &manager.message {
+                    ui.colored_label(Color32::BLUE, message);
+                }
+            });
+
+        if !open {
+            self.bookmark_manager = None;
+            return;
+        }
+        let Some(action) = action else { return };
+
+        let path = std::path::PathBuf::from(manager.transfer_path.trim());
+        let result = match action {
+            BookmarkAction::Open(url, new_tab) => {
+                self.open_bookmark(&url, new_tab);
+                return;
+            }
+            BookmarkAction::Edit(index) => {
+                if let Some(bookmark) = self.bookmarks.bookmarks().get(index) {
+                    self.bookmark_draft = Some(BookmarkDraft::new(Some(index), bookmark));
+                }
+                return;
+            }
+            BookmarkAction::Remove(index) => self.bookmarks.remove(index).map(|_| "Bookmark deleted".to_string()),
+            BookmarkAction::Unlock => self.bookmarks.unlock(&manager.passphrase).map(|_| "Bookmarks unlocked".to_string()),
+            BookmarkAction::Import => self
+                .bookmarks
+                .import(&path)
+                .map(|added| format!("Imported {} bookmark(s) from {}", added, path.display())),
+            BookmarkAction::Export => self
+                .bookmarks
+                .export(&path)
+                .map(|_| format!("Exported {} bookmark(s) to {}", self.bookmarks.bookmarks().len(), path.display())),
+            BookmarkAction::SetPassphrase => self
+                .bookmarks
+                .set_passphrase(Some(&manager.passphrase))
+                .map(|_| "Bookmarks are now encrypted".to_string()),
+            BookmarkAction::RemovePassphrase => self
+                .bookmarks
+                .set_passphrase(None)
+                .map(|_| "Bookmarks are now stored unencrypted".to_string()),
+        };
+
+        let Some(manager) = self.bookmark_manager.as_mut() else { return };
+        if result.is_ok() {
+            manager.passphrase.clear();
+        }
+        manager.message = Some(result.unwrap_or_else(|e| e));
+    }
+
+    fn show_bookmark_editor(&mut self, ctx: &egui::Context) {
+        let folders = self.bookmarks.folders();
+        let Some(draft) = self.bookmark_draft.as_mut() else { return };
+        let mut open = true;
+        let mut save = false;
+        let mut remove = false;
+
+        egui::Window::new("Bookmark")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("bookmark_editor").num_columns(2).show(ui, |ui| {
+                    ui.label("Title:");
+                    ui.add(TextEdit::singleline(&mut draft.title).desired_width(300.0));
+                    ui.end_row();
+                    ui.label("Address:");
+                    ui.add(TextEdit::singleline(&mut draft.url).desired_width(300.0));
+                    ui.end_row();
+                    ui.label("Folder:");
+                    ui.horizontal(|ui| {
+                        ui.add(TextEdit::singleline(&mut draft.folder).hint_text("e.g. Reading/Blogs").desired_width(270.0));
+                        ui.menu_button("▾", |ui| {
+                            if ui.button("(top level)").clicked() {
+                                draft.folder.clear();
+                            }
+                            for folder in &folders {
+                                if ui.button(folder).clicked() {
+                                    draft.folder = folder.clone();
+                                }
+                            }
+                        });
+                    });
+                    ui.end_row();
+                    ui.label("Tags:");
+                    ui.add(TextEdit::singleline(&mut draft.tags).hint_text("comma separated").desired_width(300.0));
+                    ui.end_row();
+                });
+
+                if let Some(error) = &draft.error {
+                    ui.colored_label(Color32::RED, error);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        save = true;
+                    }
+                    if draft.index.is_some() && ui.button("Remove").clicked() {
+                        remove = true;
                     }
+                });
+            });
+
+        if !open {
+            self.bookmark_draft = None;
+            return;
+        }
+        let result = match (draft.index, save, remove) {
+            (Some(index), _, true) => self.bookmarks.remove(index),
+            (index, true, _) => draft.bookmark().and_then(|bookmark| match index {
+                Some(index) => self.bookmarks.update(index, bookmark),
+                None => self.bookmarks.add(bookmark),
+            }),
+            _ => return,
+        };
+        match result {
+            Ok(()) => self.bookmark_draft = None,
+            Err(e) => draft.error = Some(e),
+        }
+    }
+
+    fn show_settings(&mut self, ctx: &egui::Context) {
+        let Some(settings) = self.settings.as_mut() else { return };
+        let mut open = true;
+        let mut save = None;
+
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Entry gateway");
+                ui.radio_value(&mut settings.mode, GatewayMode::Default, "Let the Nym SDK choose");
+                ui.radio_value(&mut settings.mode, GatewayMode::Specific, "This gateway:");
+                if settings.mode == GatewayMode::Specific {
+                    ui.add(
+                        TextEdit::singleline(&mut settings.specific)
+                            .hint_text("gateway identity key")
+                            .desired_width(400.0),
+                    );
+                }
+                ui.radio_value(&mut settings.mode, GatewayMode::RandomFrom, "A random one of these gateways:");
+                if settings.mode == GatewayMode::RandomFrom {
+                    ui.add(
+                        TextEdit::multiline(&mut settings.candidates)
+                            .hint_text("one identity key per line")
+                            .desired_width(400.0),
+                    );
+                }
+
+                if let Some(error) = &settings.error {
+                    ui.colored_label(Color32::RED, error);
+                }
+                ui.separator();
+                ui.label("A new gateway is used from the next connection on.");
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        save = Some(false);
+                    }
+                    if ui.button("Save and reconnect").clicked() {
+                        save = Some(true);
+                    }
+                });
+            });
+
+        if !open {
+            self.settings = None;
+            return;
+        }
+        let Some(reconnect) = save else { return };
+
+        let choice = match settings.choice() {
+            Ok(choice) => choice,
+            Err(e) => {
+                settings.error = Some(e);
+                return;
+            }
+        };
+        if let Err(e) = Self::save_gateway_choice(&choice) {
+            settings.error = Some(format!("Could not save the settings: {}", e));
+            return;
+        }
+        self.gateway_choice = choice;
+        self.settings = None;
+        if reconnect {
+            self.reconnect();
+        }
+    }
+
+    // Drops the current mixnet client and connects again with the gateway settings
+    fn reconnect(&mut self) {
+        let pending: Vec<u64> = self.in_flight.keys().copied().collect();
+        for id in pending {
+            self.fail_request(id, "Connection closed to change the gateway".to_string());
+        }
+        if let Some(sender) = GUI_TO_MIXNET_SENDER.get() {
+            sender.lock().unwrap().take();
+        }
+
+        self.client_address.clear();
+        self.loading = true;
+        self.connection_status = "Reconnecting...".to_string();
+        self.start_connection();
+    }
+
+    fn show_signature_indicator(&self, ui: &mut Ui) {
+        let Some(status) = &self.tab().page_signature else { return };
+        match status {
+            SignatureStatus::Verified(site_key) => {
+                ui.colored_label(Color32::DARK_GREEN, "🔒 Verified")
+                    .on_hover_text(format!("Signed by site key {}", site_key));
+            }
+            SignatureStatus::Unsigned => {
+                ui.colored_label(Color32::GRAY, "Unverified")
+                    .on_hover_text("The server did not sign this page");
+            }
+            SignatureStatus::Invalid(reason) => {
+                ui.colored_label(Color32::RED, "⚠ Bad signature")
+                    .on_hover_text(format!("This page may have been tampered with: {}", reason));
+            }
+        }
+    }
+
+    /// Moves `offset` pages back (negative) or forward in the tab's history.
+    fn traverse(&mut self, tab: TabId, offset: isize) {
+        let Some(current) = self.tab_mut(tab) else { return };
+        let Some(entry) = current.history.go(offset) else { return };
+        let shown = (
+            std::mem::replace(&mut current.server_address, entry.server),
+            std::mem::replace(&mut current.address_bar, entry.page),
+        );
+        // Stay where we were if the page could not even be asked for
+        if !self.load_address(tab) {
+            if let Some(current) = self.tab_mut(tab) {
+                current.history.go(-offset);
+                (current.server_address, current.address_bar) = shown;
+            }
+        }
+    }
+
+    fn check_requests(&mut self) {
+        let expired: Vec<(u64, String)> = self.in_flight
+            .iter()
+            .filter(|(_, request)| request.last_activity().elapsed() > REQUEST_TIMEOUT)
+            .map(|(id, request)| (*id, request.path.clone()))
+            .collect();
+
+        for (id, path) in expired {
+            self.fail_request(id, format!("Request for '{}' timed out", path));
+        }
+
+        // Ask again for the chunks of stalled transfers
+        let active: Vec<u64> = self.tabs.iter().filter_map(|tab| tab.active_request).collect();
+        for id in active {
+            self.resend_missing_chunks(id);
+        }
+    }
+
+    fn resend_missing_chunks(&mut self, id: u64) {
+        let Some(request) = self.in_flight.get_mut(&id) else { return };
+        let mut retry = request.request(id);
+        let Some(assembler) = request.assembler.as_mut() else { return };
+        if assembler.last_activity.elapsed() < CHUNK_RETRY_AFTER || request.retransmissions >= MAX_CHUNK_RETRIES {
+            return;
+        }
+
+        retry.headers.push((
+            chunking::HEADER_RESEND_CHUNKS.to_string(),
+            chunking::format_resend_list(&assembler.missing()),
+        ));
+        assembler.last_activity = Instant::now();
+        request.retransmissions += 1;
+        println!("Requesting {} missing chunk(s) of '{}'", assembler.missing().len(), request.path);
+
+        if let Err(e) = Self::dispatch(&request.server, &retry) {
+            self.fail_request(id, e);
+        }
+    }
+
+    fn fail_request(&mut self, id: u64, error: String) {
+        let Some(request) = self.in_flight.remove(&id) else { return };
+        match request.purpose {
+            RequestPurpose::Asset(uri) => self.asset_loader.finish(&uri, Err(error)),
+            RequestPurpose::Page(tab) => {
+                let Some(current) = self.tab_mut(tab) else { return };
+                if current.active_request == Some(id) {
+                    current.active_request = None;
+                    current.page_loading = false;
+                    current.error = Some(error);
                 }
             }
         }
     }
 
+    fn quarantine_message(&mut self, from: String, reason: String) {
+        eprintln!("Dropping message from {}: {}", from, reason);
+        if self.quarantine.len() == QUARANTINE_SIZE {
+            self.quarantine.pop_front();
+        }
+        self.quarantine.push_back(QuarantinedMessage { from, reason });
+    }
+
     fn show_welcome_page(&self, ui: &mut Ui) {
         ui.vertical_centered(|ui| {
             ui.heading("NymView for Nym Mixnet");
@@ -541,6 +2382,70 @@ impl NymMixnetBrowser {
     }
 }
 
+// Bookmarks of one folder in the bookmarks bar, with its subfolders as submenus
+fn bookmark_folder_menu(ui: &mut Ui, bookmarks: &[Bookmark], folders: &[String], folder: &str, open: &mut Option<(String, bool)>) {
+    let prefix = format!("{}/", folder);
+    for child in folders.iter().filter(|child| child.strip_prefix(&prefix).is_some_and(|name| !name.contains('/'))) {
+        ui.menu_button(format!("📁 {}", &child[prefix.len()..]), |ui| {
+            bookmark_folder_menu(ui, bookmarks, folders, child, open);
+        });
+    }
+    for bookmark in bookmarks.iter().filter(|bookmark| bookmark.folder == folder) {
+        bookmark_button(ui, bookmark, open);
+    }
+}
+
+// A middle click opens the bookmark in a new tab
+fn bookmark_button(ui: &mut Ui, bookmark: &Bookmark, open: &mut Option<(String, bool)>) {
+    let response = ui.button(&bookmark.title).on_hover_text(&bookmark.url);
+    if response.clicked() || response.middle_clicked() {
+        *open = Some((bookmark.url.clone(), response.middle_clicked()));
+    }
+}
+
+// Back or forward button. A click moves one page, a long press or right click
+// lists the pages in that direction; returns how many steps to take.
+fn history_button(ui: &mut Ui, text: &str, hover: &str, entries: &[HistoryEntry]) -> Option<usize> {
+    let response = ui.add_enabled(!entries.is_empty(), egui::Button::new(text)).on_hover_text(hover);
+    let mut steps = response.clicked().then_some(1);
+
+    // Held too long to count as a click
+    let held = response.is_pointer_button_down_on();
+    let max_click = ui.ctx().options(|options| options.input_options.max_click_duration);
+    let long_press = held && ui.input(|input| {
+        input.pointer.press_start_time().is_some_and(|start| input.time - start >= max_click)
+    });
+    if held {
+        ui.ctx().request_repaint_after(Duration::from_millis(100));
+    }
+    let open = long_press || response.secondary_clicked() || response.long_touched();
+
+    egui::Popup::menu(&response)
+        .open_memory(open.then_some(egui::SetOpenCommand::Bool(true)))
+        .show(|ui| {
+            for (index, entry) in entries.iter().enumerate() {
+                let button = ui.button(entry.label()).on_hover_text(format!("nym://{}/{}", entry.server, entry.page));
+                if button.clicked() {
+                    steps = Some(index + 1);
+                }
+            }
+        });
+    steps
+}
+
+fn format_age(since: u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let seconds = now.saturating_sub(since);
+    match seconds {
+        0..=3599 => format!("{} minute(s)", seconds / 60),
+        3600..=86399 => format!("{} hour(s)", seconds / 3600),
+        _ => format!("{} day(s)", seconds / 86400),
+    }
+}
+
 // App Trait Implementation for eframe
 impl App for NymMixnetBrowser {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
@@ -553,20 +2458,86 @@ impl App for NymMixnetBrowser {
 impl Clone for NymMixnetBrowser {
     fn clone(&self) -> Self {
         Self {
-            address_bar: self.address_bar.clone(),
-            current_content: self.current_content.clone(),
             loading: self.loading,
-            page_loading: self.page_loading,
-            error: self.error.clone(),
             connection_status: self.connection_status.clone(),
-            server_address: self.server_address.clone(),
             client_address: self.client_address.clone(),
             message_receiver: None,
             message_sender: None,
-            history: self.history.clone(),
             connection_attempted: self.connection_attempted,
-            md_cache: CommonMarkCache::default(),
+            tabs: self.tabs.clone(),
+            active_tab: self.active_tab,
+            next_tab_id: self.next_tab_id,
             pending_navigation: None,
+            in_flight: HashMap::new(),
+            quarantine: VecDeque::new(),
+            asset_loader: Arc::new(NymAssetLoader::default()),
+            pins: self.pins.clone(),
+            show_site_info: false,
+            gateway_choice: self.gateway_choice.clone(),
+            settings: None,
+            bookmarks: self.bookmarks.clone(),
+            bookmark_manager: None,
+            bookmark_draft: None,
+            petnames: self.petnames.clone(),
+            petname_form: PetnameForm::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "EvchCCf8k1k5nM2X.GcNNjHz1YVK1aB3b@DK46aDSsaYJsqmRP";
+
+    #[test]
+    fn relative_links_resolve_against_the_page_directory() {
+        assert_eq!(NymMixnetBrowser::resolve_relative("blog/post1", "post2"), "blog/post2");
+        assert_eq!(NymMixnetBrowser::resolve_relative("blog/post1", "./post2"), "blog/post2");
+        assert_eq!(NymMixnetBrowser::resolve_relative("blog/", "./post1"), "blog/post1");
+        assert_eq!(NymMixnetBrowser::resolve_relative("blog/post1", "../about"), "about");
+        assert_eq!(NymMixnetBrowser::resolve_relative("/blog/post1", "img/logo.png"), "blog/img/logo.png");
+        // Directories keep their slash, the site root stays empty
+        assert_eq!(NymMixnetBrowser::resolve_relative("blog/post1", "archive/"), "blog/archive/");
+        assert_eq!(NymMixnetBrowser::resolve_relative("blog/post1", "../"), "");
+        // Going up past the root stops there
+        assert_eq!(NymMixnetBrowser::resolve_relative("blog/post1", "../../../about"), "about");
+        assert_eq!(NymMixnetBrowser::resolve_relative("", "../secret"), "secret");
+    }
+
+    #[test]
+    fn links_with_a_scheme_are_external() {
+        assert!(NymMixnetBrowser::is_external_link("https://example.com"));
+        assert!(NymMixnetBrowser::is_external_link("mailto:alice@example.com"));
+        assert!(NymMixnetBrowser::is_external_link("git+ssh://host/repo"));
+        assert!(!NymMixnetBrowser::is_external_link("about"));
+        assert!(!NymMixnetBrowser::is_external_link("blog/post1"));
+        assert!(!NymMixnetBrowser::is_external_link(":about"));
+        assert!(!NymMixnetBrowser::is_external_link("my page:1"));
+    }
+
+    #[test]
+    fn clicked_links_lead_to_pages_addresses_or_outside() {
+        let mut petnames = PetnameStore::default();
+        petnames.set("alice-blog", ADDRESS).unwrap();
+        let target = |href: &str| NymMixnetBrowser::link_target("blog/post1", href, &petnames);
+
+        assert_eq!(target("post2"), Some(LinkTarget::Page("blog/post2".to_string())));
+        assert_eq!(target("post2#comments"), Some(LinkTarget::Page("blog/post2".to_string())));
+        assert_eq!(target("/about"), Some(LinkTarget::Page("about".to_string())));
+        assert_eq!(target("/"), Some(LinkTarget::Page(String::new())));
+        assert_eq!(target("#comments"), None);
+        assert_eq!(target("  "), None);
+        assert_eq!(
+            target("mailto:alice@example.com"),
+            Some(LinkTarget::External("mailto:alice@example.com".to_string()))
+        );
+
+        let url = format!("nym://{}/about", ADDRESS);
+        assert_eq!(target(&url), Some(LinkTarget::Address(url.clone())));
+        assert_eq!(target("nym://alice-blog/about"), Some(LinkTarget::Address(url)));
+        // Without a dot and not a petname it is a page of this site
+        assert_eq!(target("nym://about"), Some(LinkTarget::Page("about".to_string())));
+        assert_eq!(target("nym://bob-blog/"), Some(LinkTarget::Page("bob-blog/".to_string())));
+    }
+}
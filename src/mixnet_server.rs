@@ -1,25 +1,66 @@
+use ed25519_zebra::SigningKey;
 use nym_sdk::mixnet;
-use nym_sdk::mixnet::MixnetMessageSender;
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use tokio::sync::RwLock;
 use std::sync::Arc;
-use crate::config;
-use crate::default_page;
+use crate::admin::{self, AdminAuth};
+use crate::admission::Admission;
+use crate::chunking;
+use crate::config::{LogConfig, ServerConfig};
+use crate::gateway;
+use crate::protocol::{self, Request, Response, Status};
+use crate::rate_limit::{LimitScope, RateLimiter};
+use crate::signing;
+use crate::site_cache::SiteCache;
+use crate::site_paths;
+use crate::site_watcher::SiteWatcher;
+use crate::transport::{Incoming, NymTransport, Transport};
+use crate::worker_pool::{PoolConfig, PoolMetrics, WorkerPool};
 
-pub struct NymMixnetServer {
-    nym_client: mixnet::MixnetClient,
+pub struct NymMixnetServer<T: Transport = NymTransport> {
+    // One per site, in the order of `state.sites`
+    transports: Vec<T>,
+    state: Arc<ServerState>,
+    pool_config: PoolConfig,
+    _watchers: Vec<SiteWatcher>,
+}
+
+// Everything the workers need to answer requests for all hosted sites
+pub struct ServerState {
+    pub sites: Vec<Site>,
+    pub logging: LogConfig,
+    pub metrics: PoolMetrics,
+}
+
+// One hosted site: its own Nym identity, pages, site key and access policy
+pub struct Site {
+    // Prefix for log lines, empty when only one site is hosted
+    log_prefix: String,
     sites_dir: PathBuf,
-    pub nym_address: String,
-    cache: Arc<RwLock<HashMap<String, String>>>,
+    cache: Arc<RwLock<SiteCache>>,
+    admin_auth: AdminAuth,
+    site_key: SigningKey,
+    rate_limiter: RateLimiter,
+    pub admission: Admission,
 }
 
 impl NymMixnetServer {
-    pub async fn new(sites_directory: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    /// Connects one mixnet client per site and sets up the server for all of them.
+    pub async fn new(configs: &[ServerConfig]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut sites = Vec::new();
+        for config in configs {
+            sites.push((Self::connect(config).await?, config.clone()));
+        }
+        Self::with_transports(sites).await
+    }
+    
+    async fn connect(config: &ServerConfig) -> Result<NymTransport, Box<dyn std::error::Error>> {
         // PERSISTENT CLIENT with configuration directory
-        let config_dir = config::ensure_config_dir()?;
-        let client_path = config_dir.join("mixnet_client");
+        fs::create_dir_all(&config.identity_dir)?;
+        println!("Persistence directory: {:?}", config.identity_dir);
+        let client_path = config.identity_dir.join("mixnet_client");
                 
         // FIXED: Final API with type conversion
         let storage_paths = nym_sdk::mixnet::StoragePaths::new_from_dir(&client_path)?;
@@ -28,159 +69,408 @@ impl NymMixnetServer {
             &Default::default(),
         ).await?;
         
-        let client = mixnet::MixnetClientBuilder::new_with_storage(storage)
-            .build()?;
+        config.gateway.validate()?;
+        let remembered = gateway::load(&config.identity_dir.join("gateway"))?.into_iter().next();
+        let mut builder = mixnet::MixnetClientBuilder::new_with_storage(storage);
+        if let Some(gateway) = config.gateway.pick(remembered.as_deref()) {
+            if let Some(previous) = remembered.as_ref().filter(|remembered| **remembered != gateway) {
+                println!("Moving this identity from gateway {} to {}", previous, gateway);
+            }
+            builder = builder.request_gateway(gateway);
+        }
+        let client = builder.build()?;
         
         let connected_client = client.connect_to_mixnet().await?;
-        let nym_address = connected_client.nym_address().to_string();
         
-        let sites_dir = PathBuf::from(sites_directory);
-        if !sites_dir.exists() {
-            fs::create_dir_all(&sites_dir)?;
-            println!("Pages directory created: {:?}", sites_dir);
+        Ok(NymTransport::new(connected_client))
+    }
+}
+
+impl<T: Transport + 'static> NymMixnetServer<T> {
+    /// Sets up a server for one site on an already connected transport.
+    pub async fn with_transport(transport: T, config: &ServerConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_transports(vec![(transport, config.clone())]).await
+    }
+    
+    /// Sets up a server hosting one site per transport. The sites share the
+    /// worker pool and logging settings of the first one.
+    pub async fn with_transports(sites: Vec<(T, ServerConfig)>) -> Result<Self, Box<dyn std::error::Error>> {
+        let Some((_, first)) = sites.first() else {
+            return Err("No sites to host".into());
+        };
+        let pool_config = first.pool;
+        let logging = first.logging.clone();
+        
+        let mut identity_dirs = HashSet::new();
+        for (_, config) in &sites {
+            if !identity_dirs.insert(&config.identity_dir) {
+                return Err(format!("Two sites share the identity directory {:?}", config.identity_dir).into());
+            }
+            if config.pool != pool_config || config.logging != logging {
+                println!(
+                    "Workers and logging are shared by all sites, profile '{}' uses those of '{}'",
+                    config.profile, first.profile
+                );
+            }
         }
         
-        let cache = Self::load_sites_into_cache(&sites_dir).await?;
+        let multi_site = sites.len() > 1;
+        let mut transports = Vec::new();
+        let mut hosted = Vec::new();
+        let mut watchers = Vec::new();
+        for (transport, config) in sites {
+            let log_prefix = if multi_site { format!("[{}] ", config.profile) } else { String::new() };
+            let (site, watcher) = Site::open(&config, transport.address(), log_prefix).await?;
+            transports.push(transport);
+            hosted.push(site);
+            watchers.extend(watcher);
+        }
         
-        println!("NymView Server started: nym://{}", nym_address);
-        println!("Hosting from: {:?}", sites_dir);
+        let state = ServerState {
+            sites: hosted,
+            logging,
+            metrics: PoolMetrics::default(),
+        };
         
         Ok(Self {
-            nym_client: connected_client,
-            sites_dir,
-            nym_address,
-            cache: Arc::new(RwLock::new(cache)),
+            transports,
+            state: Arc::new(state),
+            pool_config,
+            _watchers: watchers,
         })
     }
     
-    async fn load_sites_into_cache(sites_dir: &Path) -> Result<HashMap<String, String>, std::io::Error> {
-        let mut cache = HashMap::new();
-        
-        if let Ok(entries) = fs::read_dir(sites_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.is_file() {
-                    if let Some(extension) = path.extension() {
-                        if extension == "md" || extension == "markdown" {
-                            if let Ok(content) = fs::read_to_string(&path) {
-                                if let Some(file_name) = path.file_stem().and_then(|s| s.to_str()) {
-                                    cache.insert(file_name.to_string(), content);
-                                    println!("Loaded: {} -> {}", file_name, path.display());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    pub async fn start(self) -> Result<(), Box<dyn std::error::Error>> {
+        println!(
+            "Server listening ({} workers, queue of {})...",
+            self.pool_config.workers, self.pool_config.queue_size
+        );
+        let Self { transports, state, pool_config, _watchers } = self;
+        let senders = transports.iter().map(|transport| transport.sender()).collect();
+        let pool = Arc::new(WorkerPool::start(pool_config, state.clone(), senders));
         
-        // Default homepage if no index.md exists
-        if !cache.contains_key("index") {
-            cache.insert("index".to_string(), default_page::default_index().to_string());
-            println!("Serving default index page");
+        let receivers: Vec<_> = transports
+            .into_iter()
+            .enumerate()
+            .map(|(site, transport)| tokio::spawn(Self::receive(site, transport, state.clone(), pool.clone())))
+            .collect();
+        for receiver in receivers {
+            receiver.await?;
         }
-        
-        Ok(cache)
+        Ok(())
     }
     
-    pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        println!("Server listening...");
-        
-        loop {
-            if let Some(messages) = self.nym_client.wait_for_messages().await {
-                for received in messages {
-                    if let Ok(text_message) = String::from_utf8(received.message.clone()) {
-                        let (response, reply_to) = self.handle_request(&text_message).await;
-                        
-                        if let Some(target) = reply_to {
-                            match target.parse::<nym_sdk::mixnet::Recipient>() {
-                                Ok(recipient) => {
-                                    if let Err(e) = self.nym_client.send_plain_message(recipient, response).await {
-                                        eprintln!("Error sending response: {}", e);
-                                    }
-                                    // Keine "Response sent successfully" Ausgabe mehr
-                                }
-                                Err(e) => {
-                                    eprintln!("Invalid response address: {}", e);
-                                }
-                            }
-                        } else {
-                            eprintln!("No response address in request");
+    async fn receive(site: usize, mut transport: T, state: Arc<ServerState>, pool: Arc<WorkerPool<T::ReplyTag>>) {
+        let current = &state.sites[site];
+        
+        while let Some(messages) = transport.receive().await {
+            for received in messages {
+                // Checked first, so unsolved requests don't use up the rate limits.
+                // Their challenges are only answered while the outgoing queue has
+                // room (see `WorkerPool::reject`), which caps what they cost us
+                if let Err(challenge) = current.admission.admit(&received.message) {
+                    state.metrics.count_challenged();
+                    pool.reject(site, &received.message, received.reply_tag, |id| {
+                        let mut response = Response::error(
+                            id,
+                            Status::ChallengeRequired,
+                            "Server under load, solve the challenge to continue",
+                        );
+                        response.headers.extend(challenge.headers());
+                        response
+                    });
+                    continue;
+                }
+                
+                match current.rate_limiter.check(&Self::sender_key(&received)) {
+                    Ok(()) => {
+                        pool.submit(site, received.message, received.reply_tag);
+                        current.admission.observe_queue(state.metrics.queued());
+                    }
+                    Err(limited) => {
+                        state.metrics.count_rate_limited();
+                        if limited.scope == LimitScope::Global {
+                            current.admission.note_overload();
                         }
+                        // Whole seconds, rounded up so a retry at the hint succeeds
+                        let retry_after = limited.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+                        let message = current.rate_limiter.message();
+                        pool.reject(site, &received.message, received.reply_tag, |id| {
+                            let mut response = Response::error(id, Status::RateLimited, message);
+                            response.headers.push((protocol::HEADER_RETRY_AFTER.to_string(), retry_after.to_string()));
+                            response
+                        });
                     }
                 }
             }
-            
-            tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        }
+        
+        println!("{}Mixnet connection closed", current.log_prefix);
+    }
+    
+    // Who a message counts against for rate limiting
+    fn sender_key(received: &Incoming<T::ReplyTag>) -> String {
+        if let Some(reply_tag) = &received.reply_tag {
+            return format!("tag:{:?}", reply_tag);
+        }
+        match Request::decode(&received.message).ok().and_then(|request| request.reply_to) {
+            Some(address) => format!("address:{}", address.trim()),
+            None => "unknown".to_string(),
         }
     }
+}
+
+impl ServerState {
+    pub async fn handle_request(&self, site: usize, message: &[u8]) -> Option<(Vec<Vec<u8>>, Option<String>)> {
+        let site = &self.sites[site];
+        let request = Site::decode_request(message)?;
+        let response = site.process_command(&request, &self.metrics).await;
+        if self.logging.requests {
+            println!("{}{} {} -> {}", site.log_prefix, request.command, request.path, response.status);
+        }
+        site.encode_response(request, response)
+    }
     
-    async fn handle_request(&self, request: &str) -> (String, Option<String>) {
-        // Search for " FROM " from the back (in case path contains spaces)
-        if let Some(from_pos) = request.rfind(" FROM ") {
-            let actual_request = &request[..from_pos];
-            let client_address = request[from_pos + 6..].trim().to_string();
-            
-            let response = self.process_command(actual_request).await;
-            (response, Some(client_address))
+    /// Answers a message without processing it.
+    pub fn reject_request(
+        &self,
+        site: usize,
+        message: &[u8],
+        make_response: impl FnOnce(u64) -> Response,
+    ) -> Option<(Vec<Vec<u8>>, Option<String>)> {
+        let request = Site::decode_request(message)?;
+        let response = make_response(request.id);
+        self.sites[site].encode_response(request, response)
+    }
+}
+
+impl Site {
+    /// Loads the keys and pages of a site, keeping its keys in the configured identity directory.
+    async fn open(
+        config: &ServerConfig,
+        nym_address: String,
+        log_prefix: String,
+    ) -> Result<(Self, Option<SiteWatcher>), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&config.identity_dir)?;
+        let site_key = signing::load_or_create_key(&config.identity_dir.join("site_key"))?;
+        
+        // Remembered so a random pick stays put across restarts
+        if let Some(gateway) = gateway::gateway_of(&nym_address) {
+            gateway::save(&config.identity_dir.join("gateway"), &[gateway.to_string()])?;
+            println!("{}Gateway: {}", log_prefix, gateway);
+        }
+        
+        let admin_auth = AdminAuth::load(&config.admin_keys, &nym_address)?;
+        if admin_auth.key_count() == 0 {
+            println!("{}No admin keys in {:?}, admin commands are disabled", log_prefix, config.admin_keys);
         } else {
-            // Old requests or errors
-            let response = "ERROR: Request must be 'GET /path FROM your_address'".to_string();
-            (response, None)
+            println!("{}Loaded {} admin key(s)", log_prefix, admin_auth.key_count());
+        }
+        
+        let sites_dir = config.pages_dir.clone();
+        if !sites_dir.exists() {
+            fs::create_dir_all(&sites_dir)?;
+            println!("{}Pages directory created: {:?}", log_prefix, sites_dir);
+        }
+        // Watcher events carry absolute paths, so the cache keys must be derived from one too
+        let sites_dir = fs::canonicalize(&sites_dir)?;
+        
+        let cache = Arc::new(RwLock::new(Self::load_cache(sites_dir.clone()).await?));
+        
+        let watcher = match SiteWatcher::start(sites_dir.clone(), cache.clone()) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                eprintln!("{}Hot reload disabled, cannot watch {:?}: {}", log_prefix, sites_dir, e);
+                None
+            }
+        };
+        
+        println!("{}NymView Server started: nym://{}", log_prefix, nym_address);
+        println!("{}Hosting from: {:?}", log_prefix, sites_dir);
+        println!("{}Site key: {}", log_prefix, signing::public_key_hex(&site_key));
+        
+        let site = Site {
+            log_prefix,
+            sites_dir,
+            cache,
+            admin_auth,
+            site_key,
+            rate_limiter: RateLimiter::new(config.rate_limits.clone()),
+            admission: Admission::new(config.admission),
+        };
+        Ok((site, watcher))
+    }
+    
+    // Reading the pages directory blocks, so it runs off the async workers
+    async fn load_cache(sites_dir: PathBuf) -> Result<SiteCache, tokio::task::JoinError> {
+        tokio::task::spawn_blocking(move || SiteCache::load(&sites_dir)).await
+    }
+    
+    fn decode_request(message: &[u8]) -> Option<Request> {
+        match Request::decode(message) {
+            Ok(request) => Some(request),
+            Err(e) => {
+                // Without a decodable envelope there is nobody to answer
+                eprintln!("Malformed request: {}", e);
+                None
+            }
         }
     }
     
-    async fn process_command(&self, request: &str) -> String {
-        let parts: Vec<&str> = request.splitn(2, ' ').collect();
-        if parts.len() != 2 {
-            return "ERROR: Invalid request format".to_string();
+    fn encode_response(&self, request: Request, mut response: Response) -> Option<(Vec<Vec<u8>>, Option<String>)> {
+        if response.body.len() > chunking::MAX_TRANSFER_SIZE {
+            response = Response::error(
+                request.id,
+                Status::TooLarge,
+                &format!("Response of {} bytes exceeds the transfer limit", response.body.len()),
+            );
+        }
+        
+        // Signed as a whole, the browser checks the signature after reassembly
+        if let Err(e) = signing::sign_response(&mut response, &self.site_key) {
+            eprintln!("Error signing response: {}", e);
+            return None;
         }
         
-        let command = parts[0];
-        let path = parts[1].trim();
+        // A repeated request only wants the chunks that got lost on the way
+        let resend = request
+            .header(chunking::HEADER_RESEND_CHUNKS)
+            .map(chunking::parse_resend_list);
         
-        match command {
-            "GET" => self.serve_page(path).await,
-            "LIST" => self.list_pages().await,
-            "PING" => "PONG".to_string(),
-            "RELOAD" => self.reload_cache().await,
-            _ => format!("ERROR: Unknown command: {}", command),
+        let mut chunks = Vec::new();
+        for chunk in chunking::split_response(&response, resend.as_deref()) {
+            match chunk.encode() {
+                Ok(bytes) => chunks.push(bytes),
+                Err(e) => {
+                    eprintln!("Error encoding response: {}", e);
+                    return None;
+                }
+            }
         }
+        
+        let reply_to = request.reply_to.map(|address| address.trim().to_string());
+        Some((chunks, reply_to))
     }
     
-    async fn serve_page(&self, path: &str) -> String {
-        let clean_path = if path == "/" { "index" } else { path.trim_start_matches('/') };
+    async fn process_command(&self, request: &Request, metrics: &PoolMetrics) -> Response {
+        let path = request.path.trim();
         
-        let cache = self.cache.read().await;
-        match cache.get(clean_path) {
-            Some(content) => {
-                format!("OK\n{}", content)
+        if admin::is_admin_command(&request.command) {
+            if let Err(e) = self.admin_auth.verify(request) {
+                eprintln!("Rejected {} request: {}", request.command, e);
+                return Response::error(
+                    request.id,
+                    Status::Unauthorized,
+                    &format!("Unauthorized: {}", e),
+                );
             }
-            None => {
-                format!("ERROR: Page '{}' not found", clean_path)
+        }
+        
+        match request.command.as_str() {
+            "GET" => {
+                let response = self.serve_page(request.id, path).await;
+                self.with_error_page(path, response).await
             }
+            "LIST" => self.list_pages(request.id).await,
+            "PING" => Response::ok(request.id, "PONG"),
+            "RELOAD" => self.reload_cache(request.id).await,
+            "STATS" => Response::ok(request.id, metrics.summary()),
+            command => Response::error(
+                request.id,
+                Status::BadRequest,
+                &format!("Unknown command: {}", command),
+            ),
         }
     }
     
-    async fn list_pages(&self) -> String {
+    async fn serve_page(&self, id: u64, path: &str) -> Response {
+        let Some(key) = site_paths::normalize_request_path(path) else {
+            return Response::error(id, Status::BadRequest, &format!("Invalid path '{}'", path));
+        };
+        
         let cache = self.cache.read().await;
-        let pages: Vec<&str> = cache.keys().map(|k| k.as_str()).collect();
-        format!("OK\n{}", pages.join(","))
+        let page = site_paths::page_candidates(&key)
+            .iter()
+            .filter(|candidate| !site_paths::is_error_page(candidate))
+            .find_map(|candidate| cache.page(candidate));
+        let (body, mime) = if let Some(content) = page {
+            (content.as_bytes().to_vec(), protocol::MARKDOWN_MIME)
+        } else if let Some(asset) = cache.asset(&key) {
+            (asset.bytes.clone(), asset.mime)
+        } else if let Some(location) = cache.redirect(&key) {
+            let mut response = Response::error(id, Status::Redirect, &format!("Moved to {}", location));
+            response.headers.push((protocol::HEADER_LOCATION.to_string(), location));
+            return response;
+        } else {
+            return Response::error(
+                id,
+                Status::NotFound,
+                &format!("Page '{}' not found", key),
+            );
+        };
+        
+        let mut response = Response::ok(id, body);
+        response.headers.push((protocol::HEADER_CONTENT_TYPE.to_string(), mime.to_string()));
+        response
     }
     
-    async fn reload_cache(&self) -> String {
-        match Self::load_sites_into_cache(&self.sites_dir).await {
+    /// Replaces the plain text of a failed page request with the site's own
+    /// error page, keeping the status so the browser still sees an error.
+    async fn with_error_page(&self, path: &str, response: Response) -> Response {
+        if !response.status.is_error() {
+            return response;
+        }
+        let cache = self.cache.read().await;
+        let Some(page) = cache.error_page(response.status) else {
+            return response;
+        };
+        
+        let body = page
+            .replace("{{status}}", &response.status.code().to_string())
+            .replace("{{path}}", &escape_markdown(path))
+            .replace("{{message}}", &escape_markdown(&response.body_text()));
+        let mut error_page = Response::with_status(response.id, response.status, body);
+        error_page
+            .headers
+            .push((protocol::HEADER_CONTENT_TYPE.to_string(), protocol::MARKDOWN_MIME.to_string()));
+        error_page
+    }
+    
+    async fn list_pages(&self, id: u64) -> Response {
+        let cache = self.cache.read().await;
+        Response::ok(id, cache.names().join(","))
+    }
+    
+    async fn reload_cache(&self, id: u64) -> Response {
+        match Self::load_cache(self.sites_dir.clone()).await {
             Ok(new_cache) => {
+                let summary = format!(
+                    "Cache reloaded ({} pages, {} assets)",
+                    new_cache.page_count(),
+                    new_cache.asset_count()
+                );
                 let mut cache = self.cache.write().await;
                 *cache = new_cache;
-                "OK\nCache reloaded".to_string()
+                Response::ok(id, summary)
             }
-            Err(e) => format!("ERROR: Error reloading: {}", e),
+            Err(e) => Response::error(
+                id,
+                Status::ServerError,
+                &format!("Error reloading: {}", e),
+            ),
         }
     }
-    
-    pub fn get_nym_address(&self) -> &str {
-        &self.nym_address
-    }
 }
 
+
+// Requested paths are chosen by the visitor, so they must not add links or markup to the page
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_{}[]()<>#+-.!|~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
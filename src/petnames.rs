@@ -0,0 +1,371 @@
+// Local names for sites, so `nym://alice-blog/` can stand for a full address.
+//
+// Stored as one name per line: `<petname> <nym address>`. Names are only
+// known to this browser; to pass one on, it is shared as a petname card:
+//
+//   nymview-petname:1:<name>:<address>:<site key or ->:<signer>:<signature>
+//
+// signed with a key of this browser, so whoever imports the card can tell it
+// was not altered on the way and recognise who it came from.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use ed25519_zebra::{Signature, SigningKey, VerificationKey};
+
+use crate::browser_data;
+use crate::signing;
+
+const PETNAMES_FILE: &str = "petnames";
+const CARD_KEY_FILE: &str = "petname_card_key";
+
+const CARD_PREFIX: &str = "nymview-petname";
+const CARD_VERSION: &str = "1";
+const SIGNING_CONTEXT: &[u8] = b"nymview-petname-card-v1";
+
+const MAX_NAME_LEN: usize = 63;
+
+/// Whether `name` can be a petname: lowercase letters, digits and dashes,
+/// so it never looks like an address or a page.
+pub fn is_valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LEN).contains(&name.len())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+// The one rule for entries typed in and read from the petnames file:
+// names are lowercased and addresses need their `@`
+fn check_entry(name: &str, address: &str) -> Result<(String, String), String> {
+    let name = name.trim().to_ascii_lowercase();
+    let address = address.trim();
+    if !is_valid_name(&name) {
+        return Err(format!(
+            "'{}' is not a valid petname: use lowercase letters, digits and dashes",
+            name
+        ));
+    }
+    if !address.contains('@') || address.contains(char::is_whitespace) {
+        return Err(format!("'{}' is not a nym address", address));
+    }
+    Ok((name, address.to_string()))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PetnameStore {
+    path: Option<PathBuf>,
+    // Petname to full address
+    names: BTreeMap<String, String>,
+}
+
+impl PetnameStore {
+    /// Loads the petnames from the browser data directory. Without a usable
+    /// directory they are only kept for this session.
+    pub fn load() -> Self {
+        match browser_data::ensure_data_dir() {
+            Ok(dir) => Self::load_from(dir.join(PETNAMES_FILE)),
+            Err(e) => {
+                eprintln!("Petnames will not be saved: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        let mut names = BTreeMap::new();
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                for line in text.lines().filter(|line| !line.trim().is_empty()) {
+                    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                        [name, address] => match check_entry(name, address) {
+                            Ok((name, address)) => {
+                                names.insert(name, address);
+                            }
+                            Err(e) => eprintln!("Ignoring petname in {}: {}", path.display(), e),
+                        },
+                        _ => eprintln!("Ignoring invalid petname in {}: {}", path.display(), line),
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("Error reading {}: {}", path.display(), e),
+        }
+
+        Self { path: Some(path), names }
+    }
+
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        self.names.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    /// The petname given to `address`, if any.
+    pub fn name_of(&self, address: &str) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|(_, known)| known.as_str() == address.trim())
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.names.iter().map(|(name, address)| (name.as_str(), address.as_str()))
+    }
+
+    /// Replaces a petname in the host part of a `nym://` URL with its
+    /// address. Anything else comes back unchanged.
+    pub fn resolve_url(&self, url: &str) -> String {
+        let Some(rest) = url.trim().strip_prefix("nym://") else { return url.to_string() };
+        let (host, page) = rest.split_once('/').unwrap_or((rest, ""));
+        match self.resolve(host) {
+            Some(address) => format!("nym://{}/{}", address, page),
+            None => url.to_string(),
+        }
+    }
+
+    /// Names `address`, replacing whatever name it had before.
+    pub fn set(&mut self, name: &str, address: &str) -> Result<(), String> {
+        let (name, address) = check_entry(name, address)?;
+        if let Some(other) = self.resolve(&name).filter(|other| *other != address) {
+            return Err(format!("'{}' already names {}", name, other));
+        }
+
+        self.names.retain(|_, known| *known != address);
+        self.names.insert(name, address);
+        self.save();
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        if self.names.remove(&name.trim().to_ascii_lowercase()).is_some() {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else { return };
+        let text: String = self.names
+            .iter()
+            .map(|(name, address)| format!("{} {}\n", name, address))
+            .collect();
+        if let Err(e) = browser_data::write_private(path, &text) {
+            eprintln!("Error saving {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// A petname shared by someone else, with the signature checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PetnameCard {
+    pub name: String,
+    pub address: String,
+    /// Site key the sharer had pinned for the address
+    pub site_key: Option<String>,
+    /// Key the card was signed with, hex encoded
+    pub signer: String,
+}
+
+impl PetnameCard {
+    /// Writes a card for `name` signed with `key`.
+    pub fn create(name: &str, address: &str, site_key: Option<&str>, key: &SigningKey) -> String {
+        let site_key = site_key.unwrap_or("-");
+        let signature = key.sign(&signed_bytes(name, address, site_key));
+        [
+            CARD_PREFIX,
+            CARD_VERSION,
+            name,
+            address,
+            site_key,
+            &signing::public_key_hex(key),
+            &hex::encode(signature.to_bytes()),
+        ]
+        .join(":")
+    }
+
+    /// Reads a card and checks its signature.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = text.trim().split(':').collect();
+        let [CARD_PREFIX, version, name, address, site_key, signer, signature] = fields.as_slice() else {
+            return Err("This is not a petname card".to_string());
+        };
+        if *version != CARD_VERSION {
+            return Err(format!("Petname cards of version {} are not supported", version));
+        }
+        // A line break would let the card write a second line into the petnames file
+        if fields.iter().any(|field| field.chars().any(char::is_whitespace)) {
+            return Err("The card contains spaces or line breaks".to_string());
+        }
+        if !is_valid_name(name) || !address.contains('@') {
+            return Err("The card names an invalid petname or address".to_string());
+        }
+        if *site_key != "-" && !(site_key.len() == 64 && site_key.chars().all(|c| c.is_ascii_hexdigit())) {
+            return Err("The card has a malformed site key".to_string());
+        }
+
+        let key = hex::decode(signer)
+            .ok()
+            .and_then(|bytes| VerificationKey::try_from(bytes.as_slice()).ok())
+            .ok_or_else(|| "The card has a malformed signer key".to_string())?;
+        let signature = hex::decode(signature)
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+            .ok_or_else(|| "The card has a malformed signature".to_string())?;
+        key.verify(&Signature::from_bytes(&signature), &signed_bytes(name, address, site_key))
+            .map_err(|_| "The card's signature does not match, it may have been altered".to_string())?;
+
+        Ok(Self {
+            name: name.to_string(),
+            address: address.to_string(),
+            site_key: (*site_key != "-").then(|| site_key.to_ascii_lowercase()),
+            signer: signer.to_ascii_lowercase(),
+        })
+    }
+}
+
+fn signed_bytes(name: &str, address: &str, site_key: &str) -> Vec<u8> {
+    let mut bytes = SIGNING_CONTEXT.to_vec();
+    bytes.extend_from_slice(format!("{}\n{}\n{}", name, address, site_key).as_bytes());
+    bytes
+}
+
+/// Key this browser signs its petname cards with, created on first use.
+pub fn card_key() -> io::Result<SigningKey> {
+    let dir = browser_data::ensure_data_dir()?;
+    signing::load_or_create_key(&dir.join(CARD_KEY_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "EvchCCf8k1k5nM2X.GcNNjHz1YVK1aB3b@DK46aDSsaYJsqmRP";
+    const OTHER: &str = "2xU4CBE6QiiYt6Ey.E3mvZTHQCdBvhfr1@BXSALwxkNvM7gqJf";
+
+    fn site_key() -> String {
+        signing::public_key_hex(&SigningKey::new(rand::thread_rng()))
+    }
+
+    // The card with field `index` replaced
+    fn with_field(card: &str, index: usize, value: &str) -> String {
+        let mut fields: Vec<&str> = card.split(':').collect();
+        fields[index] = value;
+        fields.join(":")
+    }
+
+    #[test]
+    fn cards_carry_a_signed_petname() {
+        let key = SigningKey::new(rand::thread_rng());
+        let pinned = site_key();
+        let card = PetnameCard::create("alice-blog", ADDRESS, Some(&pinned), &key);
+        assert_eq!(
+            PetnameCard::parse(&format!("  {}\n", card)),
+            Ok(PetnameCard {
+                name: "alice-blog".to_string(),
+                address: ADDRESS.to_string(),
+                site_key: Some(pinned),
+                signer: signing::public_key_hex(&key),
+            })
+        );
+
+        let unpinned = PetnameCard::create("alice-blog", ADDRESS, None, &key);
+        assert_eq!(PetnameCard::parse(&unpinned).unwrap().site_key, None);
+    }
+
+    #[test]
+    fn altered_cards_are_refused() {
+        let key = SigningKey::new(rand::thread_rng());
+        let card = PetnameCard::create("alice-blog", ADDRESS, Some(&site_key()), &key);
+        let altered = "The card's signature does not match, it may have been altered".to_string();
+
+        assert_eq!(PetnameCard::parse(&with_field(&card, 2, "bob-blog")), Err(altered.clone()));
+        assert_eq!(PetnameCard::parse(&with_field(&card, 3, OTHER)), Err(altered.clone()));
+        assert_eq!(PetnameCard::parse(&with_field(&card, 4, &site_key())), Err(altered.clone()));
+        assert_eq!(PetnameCard::parse(&with_field(&card, 4, "-")), Err(altered.clone()));
+        assert_eq!(PetnameCard::parse(&with_field(&card, 5, &site_key())), Err(altered));
+    }
+
+    #[test]
+    fn only_well_formed_cards_of_this_version_are_read() {
+        let key = SigningKey::new(rand::thread_rng());
+        let card = PetnameCard::create("alice-blog", ADDRESS, None, &key);
+
+        assert_eq!(
+            PetnameCard::parse(&with_field(&card, 1, "2")),
+            Err("Petname cards of version 2 are not supported".to_string())
+        );
+        assert!(PetnameCard::parse(&with_field(&card, 0, "petname")).is_err());
+        assert!(PetnameCard::parse(&format!("{}:extra", card)).is_err());
+        assert!(PetnameCard::parse(&with_field(&card, 2, "Alice Blog")).is_err());
+        assert!(PetnameCard::parse(&with_field(&card, 4, "not-a-key")).is_err());
+        assert!(PetnameCard::parse(&with_field(&card, 4, &"g".repeat(64))).is_err());
+
+        // Signed, but with a line break that would end up in the petnames file
+        let sneaky = format!("{}\nbob-blog {}", ADDRESS, OTHER);
+        let card = PetnameCard::create("alice-blog", &sneaky, None, &key);
+        assert_eq!(PetnameCard::parse(&card), Err("The card contains spaces or line breaks".to_string()));
+    }
+
+    #[test]
+    fn names_stand_for_one_address_each() {
+        let mut store = PetnameStore::default();
+        store.set(" Alice-Blog ", ADDRESS).unwrap();
+        assert_eq!(store.resolve("alice-blog"), Some(ADDRESS));
+        assert_eq!(store.name_of(ADDRESS), Some("alice-blog"));
+
+        assert_eq!(store.set("alice-blog", OTHER), Err(format!("'alice-blog' already names {}", ADDRESS)));
+        assert!(store.set("alice blog", OTHER).is_err());
+        assert!(store.set("-alice", OTHER).is_err());
+        assert!(store.set("alice-docs", "nym://alice").is_err());
+        assert!(store.set("alice-docs", &format!("{}\nx {}", OTHER, OTHER)).is_err());
+
+        // Naming the address again replaces its old name
+        store.set("alice", ADDRESS).unwrap();
+        assert_eq!(store.resolve("alice-blog"), None);
+        assert_eq!(store.names().collect::<Vec<_>>(), [("alice", ADDRESS)]);
+    }
+
+    #[test]
+    fn the_file_follows_the_same_rules() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(PETNAMES_FILE);
+        fs::write(
+            &path,
+            format!("Alice-Blog {}\nno-address nym://alice\nbad_name {}\ntoo many {}\n\n", ADDRESS, OTHER, OTHER),
+        )
+        .unwrap();
+
+        let mut store = PetnameStore::load_from(path.clone());
+        assert_eq!(store.names().collect::<Vec<_>>(), [("alice-blog", ADDRESS)]);
+
+        store.set("docs", OTHER).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("alice-blog {}\ndocs {}\n", ADDRESS, OTHER));
+        store.remove(" Alice-Blog ");
+        assert_eq!(PetnameStore::load_from(path).names().collect::<Vec<_>>(), [("docs", OTHER)]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_file_is_only_readable_by_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(PETNAMES_FILE);
+        let mut store = PetnameStore::load_from(path.clone());
+        store.set("alice-blog", ADDRESS).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn petnames_in_urls_become_addresses() {
+        let mut store = PetnameStore::default();
+        store.set("alice-blog", ADDRESS).unwrap();
+
+        assert_eq!(store.resolve_url("nym://alice-blog/blog/post1"), format!("nym://{}/blog/post1", ADDRESS));
+        assert_eq!(store.resolve_url("nym://Alice-Blog"), format!("nym://{}/", ADDRESS));
+        assert_eq!(store.resolve_url("nym://bob-blog/about"), "nym://bob-blog/about");
+        assert_eq!(store.resolve_url("/alice-blog/about"), "/alice-blog/about");
+        let full = format!("nym://{}/about", OTHER);
+        assert_eq!(store.resolve_url(&full), full);
+    }
+}
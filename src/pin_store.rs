@@ -0,0 +1,184 @@
+// Site keys remembered per server address (trust on first use).
+//
+// Stored as one pin per line: `<nym address> <site key hex> <first seen>`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::browser_data;
+
+const PINS_FILE: &str = "pins";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin {
+    pub site_key: String,
+    /// Unix time the key was pinned
+    pub first_seen: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinCheck {
+    /// No pin yet; the key has just been pinned
+    FirstUse,
+    Match,
+    Changed { pinned: String },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PinStore {
+    path: Option<PathBuf>,
+    pins: BTreeMap<String, Pin>,
+}
+
+impl PinStore {
+    /// Loads the pins from the browser data directory. Without a usable
+    /// directory pins are only kept for this session.
+    pub fn load() -> Self {
+        match browser_data::ensure_data_dir() {
+            Ok(dir) => Self::load_from(dir.join(PINS_FILE)),
+            Err(e) => {
+                eprintln!("Site key pins will not be saved: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        let mut pins = BTreeMap::new();
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                for line in text.lines().filter(|line| !line.trim().is_empty()) {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    match fields.as_slice() {
+                        [address, site_key, first_seen] if is_site_key(site_key) => match first_seen.parse() {
+                            Ok(first_seen) => {
+                                pins.insert(address.to_string(), Pin {
+                                    site_key: site_key.to_ascii_lowercase(),
+                                    first_seen,
+                                });
+                            }
+                            Err(_) => eprintln!("Ignoring invalid pin in {}: {}", path.display(), line),
+                        },
+                        _ => eprintln!("Ignoring invalid pin in {}: {}", path.display(), line),
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("Error reading {}: {}", path.display(), e),
+        }
+
+        Self { path: Some(path), pins }
+    }
+
+    pub fn get(&self, address: &str) -> Option<&Pin> {
+        self.pins.get(address)
+    }
+
+    pub fn pins(&self) -> impl Iterator<Item = (&str, &Pin)> {
+        self.pins.iter().map(|(address, pin)| (address.as_str(), pin))
+    }
+
+    /// Compares a verified site key with the pin for `address`, pinning it
+    /// if the address has not been seen before.
+    pub fn check(&mut self, address: &str, site_key: &str) -> PinCheck {
+        match self.pins.get(address) {
+            Some(pin) if pin.site_key == site_key => PinCheck::Match,
+            Some(pin) => PinCheck::Changed { pinned: pin.site_key.clone() },
+            None => {
+                self.pin(address, site_key);
+                PinCheck::FirstUse
+            }
+        }
+    }
+
+    /// Pins `site_key` for `address`, replacing any earlier pin.
+    pub fn pin(&mut self, address: &str, site_key: &str) {
+        let first_seen = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.pins.insert(address.to_string(), Pin {
+            site_key: site_key.to_string(),
+            first_seen,
+        });
+        self.save();
+    }
+
+    pub fn reset(&mut self, address: &str) {
+        if self.pins.remove(address).is_some() {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else { return };
+        let text: String = self.pins
+            .iter()
+            .map(|(address, pin)| format!("{} {} {}\n", address, pin.site_key, pin.first_seen))
+            .collect();
+        if let Err(e) = browser_data::write_private(path, &text) {
+            eprintln!("Error saving {}: {}", path.display(), e);
+        }
+    }
+}
+
+// A hex encoded ed25519 public key
+fn is_site_key(text: &str) -> bool {
+    text.len() == 64 && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "EvchCCf8k1k5nM2X.GcNNjHz1YVK1aB3b@DK46aDSsaYJsqmRP";
+    const OTHER: &str = "2xU4CBE6QiiYt6Ey.E3mvZTHQCdBvhfr1@BXSALwxkNvM7gqJf";
+
+    #[test]
+    fn only_well_formed_pins_are_loaded() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(PINS_FILE);
+        let key = "ab".repeat(32);
+        fs::write(
+            &path,
+            format!(
+                "{} {} 1700000000\n{} {} soon\n{} short 1700000000\n{} {} 1700000000\n",
+                ADDRESS,
+                key.to_uppercase(),
+                OTHER,
+                key,
+                OTHER,
+                OTHER,
+                "zz".repeat(32)
+            ),
+        )
+        .unwrap();
+
+        let store = PinStore::load_from(path);
+        let pins: Vec<_> = store.pins().collect();
+        assert_eq!(pins, [(ADDRESS, &Pin { site_key: key, first_seen: 1_700_000_000 })]);
+    }
+
+    #[test]
+    fn pins_survive_a_reload() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(PINS_FILE);
+        let key = "cd".repeat(32);
+        let mut store = PinStore::load_from(path.clone());
+        assert_eq!(store.check(ADDRESS, &key), PinCheck::FirstUse);
+
+        let mut reloaded = PinStore::load_from(path.clone());
+        assert_eq!(reloaded.check(ADDRESS, &key), PinCheck::Match);
+        assert_eq!(reloaded.check(ADDRESS, &"ef".repeat(32)), PinCheck::Changed { pinned: key });
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+}
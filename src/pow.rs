@@ -0,0 +1,88 @@
+// Hashcash style proof of work for admission under load.
+//
+// The server hands out a random seed and a difficulty. A request is admitted
+// when SHA-256 over the seed, the request id, command, path and a nonce of
+// the browser's choosing starts with `difficulty` zero bits.
+
+use sha2::{Digest, Sha256};
+
+use crate::protocol::{Request, Response};
+
+pub const HEADER_POW_SEED: &str = "pow-seed";
+pub const HEADER_POW_DIFFICULTY: &str = "pow-difficulty";
+pub const HEADER_POW_NONCE: &str = "pow-nonce";
+
+// Anything harder would keep a browser busy for minutes
+pub const MAX_DIFFICULTY: u8 = 32;
+
+const STAMP_CONTEXT: &[u8] = b"nymview-pow-v1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    pub seed: String,
+    pub difficulty: u8,
+}
+
+impl Challenge {
+    /// Reads the challenge out of a 428 answer.
+    pub fn from_response(response: &Response) -> Option<Self> {
+        let seed = response.header(HEADER_POW_SEED)?.to_string();
+        let difficulty = response.header(HEADER_POW_DIFFICULTY)?.trim().parse().ok()?;
+        (difficulty <= MAX_DIFFICULTY).then_some(Self { seed, difficulty })
+    }
+
+    pub fn headers(&self) -> Vec<(String, String)> {
+        vec![
+            (HEADER_POW_SEED.to_string(), self.seed.clone()),
+            (HEADER_POW_DIFFICULTY.to_string(), self.difficulty.to_string()),
+        ]
+    }
+}
+
+fn stamp(seed: &str, request: &Request, nonce: u64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(STAMP_CONTEXT);
+    for field in [seed.as_bytes(), request.command.as_bytes(), request.path.as_bytes()] {
+        hasher.update((field.len() as u32).to_be_bytes());
+        hasher.update(field);
+    }
+    hasher.update(request.id.to_be_bytes());
+    hasher.update(nonce.to_be_bytes());
+    hasher.finalize().into()
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+pub fn is_solution(challenge: &Challenge, request: &Request, nonce: u64) -> bool {
+    leading_zero_bits(&stamp(&challenge.seed, request, nonce)) >= u32::from(challenge.difficulty)
+}
+
+/// Searches for a nonce solving `challenge` for `request`. Takes about
+/// 2^difficulty hashes, so run it off the UI thread.
+pub fn solve(challenge: &Challenge, request: &Request) -> u64 {
+    (0..=u64::MAX)
+        .find(|nonce| is_solution(challenge, request, *nonce))
+        .unwrap_or(0)
+}
+
+/// Adds the headers proving `nonce` solves `challenge` to a request.
+pub fn attach(request: &mut Request, challenge: &Challenge, nonce: u64) {
+    request.headers.retain(|(name, _)| {
+        ![HEADER_POW_SEED, HEADER_POW_DIFFICULTY, HEADER_POW_NONCE]
+            .iter()
+            .any(|pow_header| name.eq_ignore_ascii_case(pow_header))
+    });
+    request.headers.extend(challenge.headers());
+    request.headers.push((HEADER_POW_NONCE.to_string(), nonce.to_string()));
+}
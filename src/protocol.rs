@@ -0,0 +1,405 @@
+// Wire format shared by the NymView server and browser.
+//
+// Every mixnet message is a single envelope:
+//
+//   magic "NYMV" | version u8 | kind u8 | request id u64
+//
+// followed by the kind specific fields. Strings are UTF-8 with a u16 length
+// prefix, bodies carry a u32 length prefix, all integers are big endian.
+
+use std::fmt;
+
+pub const MAGIC: &[u8; 4] = b"NYMV";
+pub const VERSION: u8 = 1;
+
+const KIND_REQUEST: u8 = 1;
+const KIND_RESPONSE: u8 = 2;
+
+pub const HEADER_CONTENT_TYPE: &str = "content-type";
+pub const MARKDOWN_MIME: &str = "text/markdown";
+/// Seconds to wait before asking again, sent with 429 answers
+pub const HEADER_RETRY_AFTER: &str = "retry-after";
+/// Where a redirect points, a path on the same server or a `nym://` address
+pub const HEADER_LOCATION: &str = "location";
+
+/// Outcome of a request, sent as its HTTP style code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    Ok,
+    /// The page lives elsewhere, see the `location` header
+    Redirect,
+    BadRequest,
+    Unauthorized,
+    NotFound,
+    TooLarge,
+    /// The server is under load and wants proof of work, see `pow`
+    ChallengeRequired,
+    RateLimited,
+    ServerError,
+    /// The server is too busy to queue the request
+    Unavailable,
+    /// A code this version does not know
+    Other(u16),
+}
+
+impl Status {
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            200 => Status::Ok,
+            302 => Status::Redirect,
+            400 => Status::BadRequest,
+            401 => Status::Unauthorized,
+            404 => Status::NotFound,
+            413 => Status::TooLarge,
+            428 => Status::ChallengeRequired,
+            429 => Status::RateLimited,
+            500 => Status::ServerError,
+            503 => Status::Unavailable,
+            code => Status::Other(code),
+        }
+    }
+
+    pub fn code(self) -> u16 {
+        match self {
+            Status::Ok => 200,
+            Status::Redirect => 302,
+            Status::BadRequest => 400,
+            Status::Unauthorized => 401,
+            Status::NotFound => 404,
+            Status::TooLarge => 413,
+            Status::ChallengeRequired => 428,
+            Status::RateLimited => 429,
+            Status::ServerError => 500,
+            Status::Unavailable => 503,
+            Status::Other(code) => code,
+        }
+    }
+
+    // Only the server tells errors apart by code
+    #[allow(dead_code)]
+    pub fn is_error(self) -> bool {
+        self.code() >= 400
+    }
+
+    pub fn reason(self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::Redirect => "Redirect",
+            Status::BadRequest => "Bad Request",
+            Status::Unauthorized => "Unauthorized",
+            Status::NotFound => "Not Found",
+            Status::TooLarge => "Too Large",
+            Status::ChallengeRequired => "Challenge Required",
+            Status::RateLimited => "Too Many Requests",
+            Status::ServerError => "Server Error",
+            Status::Unavailable => "Unavailable",
+            Status::Other(_) => "Unknown Status",
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.reason())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub id: u64,
+    pub command: String,
+    pub path: String,
+    /// Nym address the response should be sent to.
+    ///
+    /// Deprecated: requests should carry reply SURBs instead, so the server
+    /// can answer without learning who is asking. Only kept for old clients.
+    pub reply_to: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub id: u64,
+    pub status: Status,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnexpectedKind(u8),
+    InvalidUtf8,
+    FieldTooLong(&'static str),
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Truncated => write!(f, "message is truncated"),
+            ProtocolError::BadMagic => write!(f, "not a NymView message"),
+            ProtocolError::UnsupportedVersion(v) => write!(f, "unsupported protocol version {}", v),
+            ProtocolError::UnexpectedKind(k) => write!(f, "unexpected message kind {}", k),
+            ProtocolError::InvalidUtf8 => write!(f, "text field is not valid UTF-8"),
+            ProtocolError::FieldTooLong(field) => write!(f, "field '{}' is too long", field),
+            ProtocolError::TrailingBytes(n) => write!(f, "{} unexpected bytes after message", n),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl Request {
+    pub fn new(id: u64, command: &str, path: &str) -> Self {
+        Self {
+            id,
+            command: command.to_string(),
+            path: path.to_string(),
+            reply_to: None,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    // Requests are only read by the server
+    #[allow(dead_code)]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, ProtocolError> {
+        let mut out = Writer::new(KIND_REQUEST, self.id);
+        out.string("command", &self.command)?;
+        out.string("path", &self.path)?;
+        // An empty string marks the absent reply address
+        out.string("reply_to", self.reply_to.as_deref().unwrap_or(""))?;
+        out.headers(&self.headers)?;
+        out.body(&self.body)?;
+        Ok(out.finish())
+    }
+
+    #[allow(dead_code)]
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let (mut input, id) = Reader::open(bytes, KIND_REQUEST)?;
+        let request = Self {
+            id,
+            command: input.string()?,
+            path: input.string()?,
+            reply_to: Some(input.string()?).filter(|address| !address.is_empty()),
+            headers: input.headers()?,
+            body: input.body()?,
+        };
+        input.finish()?;
+        Ok(request)
+    }
+}
+
+impl Response {
+    // Responses are only built by the server
+    #[allow(dead_code)]
+    pub fn ok(id: u64, body: impl Into<Vec<u8>>) -> Self {
+        Self::with_status(id, Status::Ok, body)
+    }
+
+    #[allow(dead_code)]
+    pub fn error(id: u64, status: Status, message: &str) -> Self {
+        Self::with_status(id, status, message.as_bytes())
+    }
+
+    pub fn with_status(id: u64, status: Status, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            id,
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    // Only the browser checks for success
+    #[allow(dead_code)]
+    pub fn is_ok(&self) -> bool {
+        self.status == Status::Ok
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, ProtocolError> {
+        let mut out = Writer::new(KIND_RESPONSE, self.id);
+        out.buf.extend_from_slice(&self.status.code().to_be_bytes());
+        out.headers(&self.headers)?;
+        out.body(&self.body)?;
+        Ok(out.finish())
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let (mut input, id) = Reader::open(bytes, KIND_RESPONSE)?;
+        let response = Self {
+            id,
+            status: Status::from_code(input.u16()?),
+            headers: input.headers()?,
+            body: input.body()?,
+        };
+        input.finish()?;
+        Ok(response)
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn new(kind: u8, id: u64) -> Self {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        buf.push(kind);
+        buf.extend_from_slice(&id.to_be_bytes());
+        Self { buf }
+    }
+
+    fn string(&mut self, field: &'static str, value: &str) -> Result<(), ProtocolError> {
+        let len = u16::try_from(value.len()).map_err(|_| ProtocolError::FieldTooLong(field))?;
+        self.buf.extend_from_slice(&len.to_be_bytes());
+        self.buf.extend_from_slice(value.as_bytes());
+        Ok(())
+    }
+
+    fn headers(&mut self, headers: &[(String, String)]) -> Result<(), ProtocolError> {
+        let count = u16::try_from(headers.len()).map_err(|_| ProtocolError::FieldTooLong("headers"))?;
+        self.buf.extend_from_slice(&count.to_be_bytes());
+        for (key, value) in headers {
+            self.string("header name", key)?;
+            self.string("header value", value)?;
+        }
+        Ok(())
+    }
+
+    fn body(&mut self, body: &[u8]) -> Result<(), ProtocolError> {
+        let len = u32::try_from(body.len()).map_err(|_| ProtocolError::FieldTooLong("body"))?;
+        self.buf.extend_from_slice(&len.to_be_bytes());
+        self.buf.extend_from_slice(body);
+        Ok(())
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn open(bytes: &'a [u8], expected_kind: u8) -> Result<(Self, u64), ProtocolError> {
+        let mut input = Self { bytes };
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(ProtocolError::BadMagic);
+        }
+        let version = input.u8()?;
+        if version != VERSION {
+            return Err(ProtocolError::UnsupportedVersion(version));
+        }
+        let kind = input.u8()?;
+        if kind != expected_kind {
+            return Err(ProtocolError::UnexpectedKind(kind));
+        }
+        let id = input.u64()?;
+        Ok((input, id))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.bytes.len() < len {
+            return Err(ProtocolError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ProtocolError> {
+        let raw = self.take(2)?;
+        Ok(u16::from_be_bytes([raw[0], raw[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ProtocolError> {
+        let raw = self.take(4)?;
+        Ok(u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, ProtocolError> {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(raw))
+    }
+
+    fn string(&mut self) -> Result<String, ProtocolError> {
+        let len = self.u16()? as usize;
+        let raw = self.take(len)?;
+        String::from_utf8(raw.to_vec()).map_err(|_| ProtocolError::InvalidUtf8)
+    }
+
+    fn headers(&mut self) -> Result<Vec<(String, String)>, ProtocolError> {
+        let count = self.u16()? as usize;
+        let mut headers = Vec::with_capacity(count.min(64));
+        for _ in 0..count {
+            let key = self.string()?;
+            let value = self.string()?;
+            headers.push((key, value));
+        }
+        Ok(headers)
+    }
+
+    fn body(&mut self) -> Result<Vec<u8>, ProtocolError> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn finish(self) -> Result<(), ProtocolError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(ProtocolError::TrailingBytes(self.bytes.len()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_keep_their_codes_on_the_wire() {
+        for code in [200, 302, 400, 401, 404, 413, 428, 429, 500, 503, 418] {
+            let response = Response::with_status(1, Status::from_code(code), "");
+            let decoded = Response::decode(&response.encode().unwrap()).unwrap();
+            assert_eq!(decoded.status.code(), code);
+        }
+        assert_eq!(Status::from_code(404), Status::NotFound);
+        assert_eq!(Status::from_code(418), Status::Other(418));
+        assert_eq!(Status::RateLimited.to_string(), "429 Too Many Requests");
+        assert!(Status::NotFound.is_error() && !Status::Redirect.is_error());
+    }
+}
@@ -0,0 +1,150 @@
+// Token bucket rate limiting of incoming requests.
+//
+// Every sender (reply tag, or the claimed reply address of old clients)
+// gets its own bucket, and all requests together draw from a global one.
+// Requests over the limit are answered with 429 and a `retry-after` hint.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Idle senders are forgotten once this many are tracked
+const MAX_TRACKED_SENDERS: usize = 10_000;
+
+pub const DEFAULT_PER_SENDER: BucketConfig = BucketConfig { rate: 2.0, burst: 20.0 };
+pub const DEFAULT_GLOBAL: BucketConfig = BucketConfig { rate: 50.0, burst: 200.0 };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketConfig {
+    /// Requests per second refilled into the bucket
+    pub rate: f64,
+    /// Requests that may be made at once after a quiet period
+    pub burst: f64,
+}
+
+impl BucketConfig {
+    /// `None` when `rate` is not positive, which disables the limit.
+    pub fn new(rate: f64, burst: f64) -> Option<Self> {
+        (rate > 0.0).then(|| Self {
+            rate,
+            burst: burst.max(1.0),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitConfig {
+    pub per_sender: Option<BucketConfig>,
+    pub global: Option<BucketConfig>,
+    /// Text sent back with the 429 answer
+    pub message: String,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_sender: Some(DEFAULT_PER_SENDER),
+            global: Some(DEFAULT_GLOBAL),
+            message: "Too many requests, slow down".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitScope {
+    Sender,
+    Global,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limited {
+    pub scope: LimitScope,
+    pub retry_after: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(config: &BucketConfig, now: Instant) -> Self {
+        Self {
+            tokens: config.burst,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.rate).min(config.burst);
+        self.updated = now;
+    }
+
+    /// Takes one token, or says how long until one is available.
+    fn take(&mut self, config: &BucketConfig, now: Instant) -> Result<(), Duration> {
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / config.rate))
+        }
+    }
+
+    fn is_full(&self, config: &BucketConfig) -> bool {
+        self.tokens >= config.burst
+    }
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    global: Mutex<Option<TokenBucket>>,
+    senders: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            global: Mutex::new(None),
+            senders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.config.message
+    }
+
+    /// Charges one request to `sender` and to the global budget.
+    pub fn check(&self, sender: &str) -> Result<(), Limited> {
+        let now = Instant::now();
+
+        if let Some(config) = &self.config.per_sender {
+            let mut senders = self.senders.lock().unwrap();
+            if senders.len() >= MAX_TRACKED_SENDERS {
+                senders.retain(|_, bucket| {
+                    bucket.refill(config, now);
+                    !bucket.is_full(config)
+                });
+            }
+            senders
+                .entry(sender.to_string())
+                .or_insert_with(|| TokenBucket::full(config, now))
+                .take(config, now)
+                .map_err(|retry_after| Limited { scope: LimitScope::Sender, retry_after })?;
+        }
+
+        if let Some(config) = &self.config.global {
+            self.global
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| TokenBucket::full(config, now))
+                .take(config, now)
+                .map_err(|retry_after| Limited { scope: LimitScope::Global, retry_after })?;
+        }
+
+        Ok(())
+    }
+}
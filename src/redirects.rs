@@ -0,0 +1,158 @@
+// Redirects and page aliases set up by the site owner.
+//
+// `redirects.toml` at the top of the pages directory maps old paths to new
+// ones, or to a page on another server:
+//
+//   "old-post" = "/blog/post1"
+//   "docs/" = "nym://<address>/docs/"
+//
+// A page can also list its former names in front matter:
+//
+//   ---
+//   aliases: [old-post, /2023/post]
+//   ---
+
+use std::collections::HashMap;
+
+use crate::site_paths;
+
+pub const REDIRECTS_FILE: &str = "redirects.toml";
+
+// A block between `---` lines only counts as front matter with one of these
+// keys, so a page opening with a rule and a `Note: ...` line keeps its text
+const FRONT_MATTER_KEYS: &[&str] = &["aliases", "title", "date", "description", "tags", "draft"];
+
+/// Reads `redirects.toml` into a map from request key to location.
+pub fn parse_redirects(contents: &str) -> Result<HashMap<String, String>, String> {
+    let table: HashMap<String, String> = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+    let mut redirects = HashMap::new();
+    for (from, to) in table {
+        let key = source_key(&from).ok_or_else(|| format!("invalid path '{}'", from))?;
+        let location = location(&to).ok_or_else(|| format!("invalid target '{}' for '{}'", to, from))?;
+        redirects.insert(key, location);
+    }
+    Ok(redirects)
+}
+
+/// Key a redirect or alias answers for; directories match with or without their `/`.
+pub fn source_key(path: &str) -> Option<String> {
+    let key = site_paths::normalize_request_path(path)?;
+    let key = key.trim_end_matches('/').to_string();
+    (!key.is_empty()).then_some(key)
+}
+
+/// A `nym://` address as it is, or a path on the same site.
+fn location(target: &str) -> Option<String> {
+    let target = target.trim();
+    if target.starts_with("nym://") {
+        return Some(target.to_string());
+    }
+    let key = site_paths::normalize_request_path(target)?;
+    Some(format!("/{}", key))
+}
+
+/// Path a page key is requested under, e.g. `blog/` for `blog/index`.
+pub fn page_location(key: &str) -> String {
+    match key.strip_suffix("index") {
+        Some(dir) if dir.is_empty() || dir.ends_with('/') => format!("/{}", dir),
+        _ => format!("/{}", key),
+    }
+}
+
+/// Splits the front matter off a page, returning the aliases it lists and
+/// the page without it. Pages without front matter come back unchanged, and
+/// so do pages that merely open with a `---` rule or whose block has none of
+/// the known front matter keys.
+pub fn split_front_matter(content: &str) -> (Vec<String>, &str) {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (Vec::new(), content);
+    };
+
+    let mut block = Vec::new();
+    let mut body = None;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" {
+            body = Some(&rest[offset..]);
+            break;
+        }
+        block.push(line);
+    }
+    let Some(body) = body else {
+        return (Vec::new(), content);
+    };
+    if !block.iter().all(|line| is_front_matter_line(line))
+        || !block.iter().any(|line| {
+            line.split_once(':')
+                .is_some_and(|(key, _)| FRONT_MATTER_KEYS.contains(&key.trim()))
+        })
+    {
+        return (Vec::new(), content);
+    }
+
+    let mut aliases = Vec::new();
+    let mut in_aliases = false;
+    for line in block {
+        let trimmed = line.trim();
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            if in_aliases {
+                aliases.push(unquote(item));
+            }
+            continue;
+        }
+        in_aliases = false;
+        let Some((key, value)) = trimmed.split_once(':') else { continue };
+        if key.trim() != "aliases" {
+            continue;
+        }
+        let value = value.trim();
+        match value.strip_prefix('[').and_then(|list| list.strip_suffix(']')) {
+            Some(list) => aliases.extend(list.split(',').map(unquote).filter(|alias| !alias.is_empty())),
+            None if value.is_empty() => in_aliases = true,
+            None => aliases.push(unquote(value)),
+        }
+    }
+    (aliases, body)
+}
+
+fn is_front_matter_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with("- ") {
+        return true;
+    }
+    trimmed.split_once(':').is_some_and(|(key, _)| {
+        !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+fn unquote(value: &str) -> String {
+    value.trim().trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_needs_a_known_key() {
+        let (aliases, body) = split_front_matter("---\ntitle: Post\naliases: [old, /2023/post]\n---\n# Post");
+        assert_eq!(aliases, vec!["old", "/2023/post"]);
+        assert_eq!(body, "# Post");
+
+        let (aliases, body) = split_front_matter("---\naliases:\n  - old\n---\n# Post");
+        assert_eq!(aliases, vec!["old"]);
+        assert_eq!(body, "# Post");
+
+        // Only a rule around ordinary text
+        let page = "---\nNote: this page moved\n---\n# Post";
+        assert_eq!(split_front_matter(page), (Vec::new(), page));
+        let page = "---\n# Post";
+        assert_eq!(split_front_matter(page), (Vec::new(), page));
+    }
+}
@@ -0,0 +1,202 @@
+// Session history of one tab, as back and forward stacks around the page
+// being shown. Going somewhere new drops whatever was ahead, moving back or
+// forward shifts entries between the stacks without recording anything.
+
+// Oldest pages are forgotten beyond this
+const MAX_ENTRIES: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub server: String,
+    pub page: String,
+    /// Title of the page once it was shown
+    pub title: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(server: &str, page: &str) -> Self {
+        Self {
+            server: server.to_string(),
+            page: page.to_string(),
+            title: None,
+        }
+    }
+
+    /// Text for the history menu: the page title, or else its path.
+    pub fn label(&self) -> String {
+        match &self.title {
+            Some(title) => title.clone(),
+            None => format!("/{}", self.page.trim_start_matches('/')),
+        }
+    }
+
+    fn same_page(&self, other: &HistoryEntry) -> bool {
+        self.server == other.server && self.page.trim_start_matches('/') == other.page.trim_start_matches('/')
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SessionHistory {
+    // Both stacks keep the nearest page last
+    back: Vec<HistoryEntry>,
+    current: Option<HistoryEntry>,
+    forward: Vec<HistoryEntry>,
+}
+
+impl SessionHistory {
+    /// Records a new navigation. Loading the page shown again is not one.
+    pub fn visit(&mut self, entry: HistoryEntry) {
+        if self.current.as_ref().is_some_and(|current| current.same_page(&entry)) {
+            return;
+        }
+        if let Some(current) = self.current.replace(entry) {
+            self.back.push(current);
+            if self.back.len() > MAX_ENTRIES {
+                self.back.remove(0);
+            }
+        }
+        self.forward.clear();
+    }
+
+    /// Puts `entry` in place of the current page, e.g. where a redirect led.
+    pub fn replace(&mut self, entry: HistoryEntry) {
+        self.current = Some(entry);
+    }
+
+    /// Drops the current page so the next visit does not leave it behind.
+    pub fn forget_current(&mut self) {
+        self.current = None;
+    }
+
+    /// Moves `offset` pages back (negative) or forward (positive) and
+    /// returns the page to load, or `None` when there are not that many.
+    pub fn go(&mut self, offset: isize) -> Option<HistoryEntry> {
+        let steps = offset.unsigned_abs();
+        let (from, to) = if offset < 0 {
+            (&mut self.back, &mut self.forward)
+        } else {
+            (&mut self.forward, &mut self.back)
+        };
+        if steps == 0 || steps > from.len() {
+            return None;
+        }
+
+        for _ in 0..steps {
+            let next = from.pop()?;
+            if let Some(current) = self.current.replace(next) {
+                to.push(current);
+            }
+        }
+        self.current.clone()
+    }
+
+    pub fn set_title(&mut self, title: String) {
+        if let Some(current) = self.current.as_mut() {
+            current.title = Some(title);
+        }
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    /// Pages behind the current one, nearest first.
+    pub fn back_entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.back.iter().rev()
+    }
+
+    /// Pages ahead of the current one, nearest first.
+    pub fn forward_entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.forward.iter().rev()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER: &str = "abc.def@gateway";
+
+    fn visited(pages: &[&str]) -> SessionHistory {
+        let mut history = SessionHistory::default();
+        for page in pages {
+            history.visit(HistoryEntry::new(SERVER, page));
+        }
+        history
+    }
+
+    fn pages<'a>(entries: impl Iterator<Item = &'a HistoryEntry>) -> Vec<&'a str> {
+        entries.map(|entry| entry.page.as_str()).collect()
+    }
+
+    #[test]
+    fn visiting_drops_the_pages_ahead() {
+        let mut history = visited(&["a", "b", "c"]);
+        assert_eq!(history.go(-2), Some(HistoryEntry::new(SERVER, "a")));
+        assert_eq!(pages(history.forward_entries()), ["b", "c"]);
+
+        history.visit(HistoryEntry::new(SERVER, "d"));
+        assert_eq!(pages(history.back_entries()), ["a"]);
+        assert_eq!(history.forward_entries().count(), 0);
+        assert_eq!(history.go(1), None);
+    }
+
+    #[test]
+    fn reloading_the_same_page_is_not_a_visit() {
+        let mut history = visited(&["a", "b"]);
+        history.go(-1);
+        history.visit(HistoryEntry::new(SERVER, "/a"));
+        assert_eq!(history.back_entries().count(), 0);
+        assert_eq!(pages(history.forward_entries()), ["b"]);
+
+        // The same path on another server is a different page
+        history.visit(HistoryEntry::new("other.site@gateway", "a"));
+        assert_eq!(pages(history.back_entries()), ["a"]);
+        assert_eq!(history.forward_entries().count(), 0);
+    }
+
+    #[test]
+    fn old_pages_are_forgotten() {
+        let names: Vec<String> = (0..=MAX_ENTRIES + 10).map(|i| i.to_string()).collect();
+        let history = visited(&names.iter().map(String::as_str).collect::<Vec<_>>());
+        assert_eq!(history.back_entries().count(), MAX_ENTRIES);
+        assert_eq!(history.back_entries().last().unwrap().page, "10");
+    }
+
+    #[test]
+    fn going_stays_within_the_history() {
+        let mut history = visited(&["a", "b", "c", "d"]);
+        assert_eq!(history.go(0), None);
+        assert_eq!(history.go(1), None);
+        assert_eq!(history.go(-4), None);
+        assert_eq!(pages(history.back_entries()), ["c", "b", "a"]);
+
+        assert_eq!(history.go(-3), Some(HistoryEntry::new(SERVER, "a")));
+        assert!(!history.can_go_back());
+        assert_eq!(history.go(-1), None);
+        assert_eq!(history.go(2), Some(HistoryEntry::new(SERVER, "c")));
+        assert_eq!(pages(history.back_entries()), ["b", "a"]);
+        assert_eq!(pages(history.forward_entries()), ["d"]);
+
+        // Going back and forth again leaves everything where it was
+        history.set_title("C".to_string());
+        history.go(-2);
+        assert_eq!(history.go(2).unwrap().title.as_deref(), Some("C"));
+        assert_eq!(pages(history.back_entries()), ["b", "a"]);
+        assert_eq!(pages(history.forward_entries()), ["d"]);
+    }
+
+    #[test]
+    fn replacing_and_forgetting_the_current_page() {
+        let mut history = visited(&["a", "old"]);
+        history.replace(HistoryEntry::new(SERVER, "new"));
+        history.visit(HistoryEntry::new(SERVER, "b"));
+        assert_eq!(pages(history.back_entries()), ["new", "a"]);
+
+        history.forget_current();
+        history.visit(HistoryEntry::new(SERVER, "c"));
+        assert_eq!(pages(history.back_entries()), ["new", "a"]);
+        assert_eq!(history.go(-1), Some(HistoryEntry::new(SERVER, "new")));
+        assert_eq!(pages(history.forward_entries()), ["c"]);
+    }
+}
@@ -0,0 +1,142 @@
+// Site signing keys and response signatures.
+//
+// Every response carries the site's ed25519 public key and a signature over
+// the response envelope encoded without those two headers. Responses are
+// signed before they are split into chunks, so the browser checks the
+// reassembled response.
+
+use ed25519_zebra::{Signature, SigningKey, VerificationKey};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::protocol::{ProtocolError, Response};
+
+pub const HEADER_SIGNATURE: &str = "signature";
+pub const HEADER_SITE_KEY: &str = "site-key";
+
+const SIGNING_CONTEXT: &[u8] = b"nymview-response-v1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+// Only the browser checks signatures
+#[allow(dead_code)]
+pub enum SignatureStatus {
+    /// Signed by the hex encoded key
+    Verified(String),
+    Unsigned,
+    Invalid(String),
+}
+
+pub fn parse_secret_key(text: &str) -> Option<SigningKey> {
+    let bytes: [u8; 32] = hex::decode(text.trim()).ok()?.try_into().ok()?;
+    Some(SigningKey::from(bytes))
+}
+
+/// Writes a secret key as hex to a new file, readable by the owner only
+/// from the moment it is created.
+pub fn write_secret_key(path: &Path, key: &SigningKey) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(hex::encode(key.as_bytes()).as_bytes())?;
+    file.sync_all()
+}
+
+/// Reads the site key, creating one on first start.
+pub fn load_or_create_key(path: &Path) -> io::Result<SigningKey> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse_secret_key(&text).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a valid site key", path.display()))
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let key = SigningKey::new(rand::thread_rng());
+            write_secret_key(path, &key)?;
+            println!("Created new site key: {:?}", path);
+            Ok(key)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub fn public_key_hex(key: &SigningKey) -> String {
+    hex::encode(<[u8; 32]>::from(VerificationKey::from(key)))
+}
+
+fn signed_bytes(response: &Response) -> Result<Vec<u8>, ProtocolError> {
+    let mut unsigned = response.clone();
+    unsigned.headers.retain(|(name, _)| {
+        !name.eq_ignore_ascii_case(HEADER_SIGNATURE) && !name.eq_ignore_ascii_case(HEADER_SITE_KEY)
+    });
+
+    let mut bytes = SIGNING_CONTEXT.to_vec();
+    bytes.extend_from_slice(&unsigned.encode()?);
+    Ok(bytes)
+}
+
+// Only the server signs
+#[allow(dead_code)]
+pub fn sign_response(response: &mut Response, key: &SigningKey) -> Result<(), ProtocolError> {
+    let signature = key.sign(&signed_bytes(response)?);
+    response.headers.push((HEADER_SITE_KEY.to_string(), public_key_hex(key)));
+    response.headers.push((HEADER_SIGNATURE.to_string(), hex::encode(signature.to_bytes())));
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub fn verify_response(response: &Response) -> SignatureStatus {
+    let (Some(site_key), Some(signature)) = (
+        response.header(HEADER_SITE_KEY),
+        response.header(HEADER_SIGNATURE),
+    ) else {
+        return SignatureStatus::Unsigned;
+    };
+
+    let Some(key) = hex::decode(site_key)
+        .ok()
+        .and_then(|bytes| VerificationKey::try_from(bytes.as_slice()).ok())
+    else {
+        return SignatureStatus::Invalid("malformed site key".to_string());
+    };
+    let Some(signature) = hex::decode(signature)
+        .ok()
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+    else {
+        return SignatureStatus::Invalid("malformed signature".to_string());
+    };
+    let Ok(bytes) = signed_bytes(response) else {
+        return SignatureStatus::Invalid("response cannot be encoded".to_string());
+    };
+
+    match key.verify(&Signature::from_bytes(&signature), &bytes) {
+        Ok(()) => SignatureStatus::Verified(site_key.to_ascii_lowercase()),
+        Err(_) => SignatureStatus::Invalid("signature does not match the content".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_keys_go_to_new_private_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("site_key");
+        let key = load_or_create_key(&path).unwrap();
+        assert_eq!(load_or_create_key(&path).unwrap().as_bytes(), key.as_bytes());
+        assert_eq!(
+            write_secret_key(&path, &SigningKey::new(rand::thread_rng())).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+}
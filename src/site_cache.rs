@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::assets::{self, Asset, AssetStore};
+use crate::chunking;
+use crate::default_page;
+use crate::protocol::Status;
+use crate::redirects;
+use crate::site_paths::{self, SiteEntry};
+
+// Everything a site serves, read from its pages directory
+#[derive(Debug, Default)]
+pub struct SiteCache {
+    pages: HashMap<String, String>,
+    assets: AssetStore,
+    // File each page was read from, to settle `post.md` vs `post.markdown`
+    sources: HashMap<String, PathBuf>,
+    // From `redirects.toml`, request key to location
+    redirects: HashMap<String, String>,
+    // From front matter, alias key to page key
+    aliases: HashMap<String, String>,
+}
+
+impl SiteCache {
+    pub fn load(sites_dir: &Path) -> Self {
+        let mut cache = Self::default();
+
+        let mut files = Vec::new();
+        collect_files(sites_dir, &mut files);
+        files.sort();
+        for path in files {
+            if let Some(file) = read_file(sites_dir, &path) {
+                cache.insert_file(file);
+            }
+        }
+
+        cache.ensure_index();
+        cache
+    }
+
+    pub fn page(&self, key: &str) -> Option<&str> {
+        self.pages.get(key).map(|content| content.as_str())
+    }
+
+    /// The site's own page for answers with `status`, if it has one.
+    pub fn error_page(&self, status: Status) -> Option<&str> {
+        site_paths::error_page_candidates(status.code())
+            .iter()
+            .find_map(|candidate| self.page(candidate))
+    }
+
+    /// Where a request for `key` should go instead, if the site moved it.
+    pub fn redirect(&self, key: &str) -> Option<String> {
+        let key = redirects::source_key(key)?;
+        if let Some(location) = self.redirects.get(&key) {
+            return Some(location.clone());
+        }
+        self.aliases.get(&key).map(|page| redirects::page_location(page))
+    }
+
+    pub fn asset(&self, key: &str) -> Option<&Asset> {
+        self.assets.get(key)
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn asset_count(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.pages
+            .keys()
+            .map(|k| k.as_str())
+            .chain(self.assets.names())
+            .collect()
+    }
+
+    /// Reads what a changed path holds now: the changed file, everything in
+    /// a new directory, or the pages a deleted file may have shadowed.
+    ///
+    /// Blocking, and done without the cache so it stays readable meanwhile.
+    pub fn read_change(sites_dir: &Path, path: &Path) -> PathChange {
+        let (removed, files) = match fs::symlink_metadata(path).map(|metadata| metadata.file_type()) {
+            Ok(file_type) if file_type.is_file() => (false, vec![path.to_path_buf()]),
+            Ok(file_type) if file_type.is_dir() => {
+                let mut files = Vec::new();
+                collect_files(path, &mut files);
+                files.sort();
+                (false, files)
+            }
+            // Symlinks and other special files are never served
+            Ok(_) => (false, Vec::new()),
+            Err(_) => {
+                let siblings = ["md", "markdown"]
+                    .iter()
+                    .map(|extension| path.with_extension(extension))
+                    .filter(|sibling| sibling != path && sibling.is_file())
+                    .collect();
+                (true, siblings)
+            }
+        };
+
+        PathChange {
+            path: path.to_path_buf(),
+            removed,
+            files: files.iter().filter_map(|file| read_file(sites_dir, file)).collect(),
+        }
+    }
+
+    /// Brings the entries for a changed path up to date with what
+    /// `read_change` found: new and changed files are taken in, deleted
+    /// ones dropped.
+    pub fn apply_change(&mut self, sites_dir: &Path, change: PathChange) {
+        if change.removed {
+            self.remove_path(sites_dir, &change.path, change.files);
+        } else {
+            for file in change.files {
+                self.insert_file(file);
+            }
+        }
+
+        self.ensure_index();
+    }
+
+    fn insert_file(&mut self, file: SiteFile) {
+        match file {
+            SiteFile::Page { key, path, content } => {
+                if let Some(existing) = self.sources.get(&key).filter(|existing| **existing != path) {
+                    eprintln!(
+                        "Both {} and {} map to '{}'; the .md file is served",
+                        existing.display(),
+                        path.display(),
+                        key
+                    );
+                    if existing.extension().is_some_and(|extension| extension == "md") {
+                        return;
+                    }
+                }
+                println!("Loaded: {} -> {}", key, path.display());
+                let (aliases, body) = redirects::split_front_matter(&content);
+                self.set_aliases(&key, aliases);
+                self.pages.insert(key.clone(), body.to_string());
+                self.sources.insert(key, path);
+            }
+            SiteFile::Redirects { path, redirects } => {
+                println!("Loaded {} redirect(s) from {}", redirects.len(), path.display());
+                self.redirects = redirects;
+            }
+            SiteFile::Asset { key, path, asset } => {
+                println!("Loaded asset: {} ({}) -> {}", key, asset.mime, path.display());
+                self.assets.insert(key, asset);
+            }
+        }
+    }
+
+    // `siblings` are the pages that share the path's stem, read by `read_change`
+    fn remove_path(&mut self, sites_dir: &Path, path: &Path, siblings: Vec<SiteFile>) {
+        match site_paths::entry_for(sites_dir, path) {
+            Some(SiteEntry::Page(key)) if self.sources.get(&key).is_some_and(|source| source == path) => {
+                self.pages.remove(&key);
+                self.sources.remove(&key);
+                self.set_aliases(&key, Vec::new());
+                println!("Removed: {}", key);
+
+                // A `post.markdown` shadowed by the deleted `post.md` takes over
+                for sibling in siblings {
+                    self.insert_file(sibling);
+                }
+            }
+            Some(SiteEntry::Asset(key)) if key == redirects::REDIRECTS_FILE => {
+                self.redirects.clear();
+                println!("Removed redirects");
+            }
+            Some(SiteEntry::Asset(key)) => {
+                let removed = self.assets.remove(&key);
+                if removed.is_some() {
+                    println!("Removed asset: {}", key);
+                }
+            }
+            _ => {}
+        }
+
+        // The path may also have been a whole directory
+        let Ok(relative) = path.strip_prefix(sites_dir) else {
+            return;
+        };
+        let Some(relative) = relative.to_str() else {
+            return;
+        };
+        let prefix = format!("{}/", relative.replace(std::path::MAIN_SEPARATOR, "/"));
+
+        let removed: Vec<String> = self.pages
+            .keys()
+            .filter(|key| key.starts_with(&prefix))
+            .cloned()
+            .collect();
+        for key in removed {
+            self.pages.remove(&key);
+            self.sources.remove(&key);
+            self.set_aliases(&key, Vec::new());
+            println!("Removed: {}", key);
+        }
+        self.assets.retain(|key| {
+            let keep = !key.starts_with(&prefix);
+            if !keep {
+                println!("Removed asset: {}", key);
+            }
+            keep
+        });
+    }
+
+    fn set_aliases(&mut self, page: &str, aliases: Vec<String>) {
+        self.aliases.retain(|_, target| target != page);
+        for alias in aliases {
+            match redirects::source_key(&alias) {
+                Some(alias) => {
+                    self.aliases.insert(alias, page.to_string());
+                }
+                None => eprintln!("Ignoring invalid alias '{}' of '{}'", alias, page),
+            }
+        }
+    }
+
+    fn ensure_index(&mut self) {
+        // Default homepage if no index.md exists
+        if !self.pages.contains_key("index") {
+            self.pages.insert("index".to_string(), default_page::default_index().to_string());
+            println!("Serving default index page");
+        }
+    }
+}
+
+/// What a changed path holds, read from disk but not yet in the cache.
+pub struct PathChange {
+    path: PathBuf,
+    removed: bool,
+    // For a removed path, the pages it may have shadowed
+    files: Vec<SiteFile>,
+}
+
+// A file from the pages directory, read and ready to go into a cache
+enum SiteFile {
+    Page { key: String, path: PathBuf, content: String },
+    Redirects { path: PathBuf, redirects: HashMap<String, String> },
+    Asset { key: String, path: PathBuf, asset: Asset },
+}
+
+fn read_file(sites_dir: &Path, path: &Path) -> Option<SiteFile> {
+    match site_paths::entry_for(sites_dir, path)? {
+        SiteEntry::Page(key) => match fs::read_to_string(path) {
+            Ok(content) => Some(SiteFile::Page { key, path: path.to_path_buf(), content }),
+            Err(e) => {
+                eprintln!("Error reading {}: {}", path.display(), e);
+                None
+            }
+        },
+        SiteEntry::Asset(key) if key == redirects::REDIRECTS_FILE => {
+            match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|contents| redirects::parse_redirects(&contents)) {
+                Ok(redirects) => Some(SiteFile::Redirects { path: path.to_path_buf(), redirects }),
+                Err(e) => {
+                    eprintln!("Ignoring {}: {}", path.display(), e);
+                    None
+                }
+            }
+        }
+        SiteEntry::Asset(key) => match fs::read(path) {
+            Ok(bytes) if bytes.len() > chunking::MAX_TRANSFER_SIZE => {
+                eprintln!("Skipping {}: larger than the transfer limit", path.display());
+                None
+            }
+            Ok(bytes) => {
+                let asset = Asset { bytes, mime: assets::mime_type(path) };
+                Some(SiteFile::Asset { key, path: path.to_path_buf(), asset })
+            }
+            Err(e) => {
+                eprintln!("Error reading {}: {}", path.display(), e);
+                None
+            }
+        },
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        // file_type() does not follow symlinks, so links out of the site are never served
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            collect_files(&entry.path(), files);
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::{Component, Path};
+
+// Mapping between files under the pages directory and the paths visitors request
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SiteEntry {
+    /// Markdown page, keyed without its extension (`blog/post1`)
+    Page(String),
+    /// Any other file, keyed with its extension (`img/logo.png`)
+    Asset(String),
+}
+
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "md" || extension == "markdown")
+}
+
+/// Works out the key a file below `sites_dir` is served under.
+///
+/// Returns `None` for paths outside the directory, hidden files and names
+/// that are not valid UTF-8.
+pub fn entry_for(sites_dir: &Path, path: &Path) -> Option<SiteEntry> {
+    let relative = path.strip_prefix(sites_dir).ok()?;
+
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => {
+                let part = part.to_str()?;
+                if part.starts_with('.') {
+                    return None;
+                }
+                parts.push(part.to_string());
+            }
+            _ => return None,
+        }
+    }
+
+    if is_markdown(path) {
+        let stem = path.file_stem()?.to_str()?.to_string();
+        *parts.last_mut()? = stem;
+        Some(SiteEntry::Page(parts.join("/")))
+    } else if parts.is_empty() {
+        None
+    } else {
+        Some(SiteEntry::Asset(parts.join("/")))
+    }
+}
+
+/// Turns a requested path into a cache key.
+///
+/// Empty and `.` segments are dropped, a trailing `/` is kept to mark a
+/// directory. Anything that could climb out of the site (`..`) or name a
+/// drive or a Windows path is rejected.
+pub fn normalize_request_path(path: &str) -> Option<String> {
+    let path = path.trim();
+    if path.contains('\\') || path.contains('\0') {
+        return None;
+    }
+
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => continue,
+            ".." => return None,
+            part if part.contains(':') => return None,
+            part => parts.push(part),
+        }
+    }
+
+    let mut key = parts.join("/");
+    if path.ends_with('/') && !key.is_empty() {
+        key.push('/');
+    }
+    Some(key)
+}
+
+/// Page keys to try for a normalized request, most specific first.
+pub fn page_candidates(key: &str) -> Vec<String> {
+    if key.is_empty() {
+        vec!["index".to_string()]
+    } else if let Some(dir) = key.strip_suffix('/') {
+        vec![format!("{}/index", dir)]
+    } else {
+        vec![key.to_string(), format!("{}/index", key)]
+    }
+}
+
+/// Whether a page key names a custom error page (`404`, `500`, `error`).
+///
+/// These are only served in place of a failed request, never on their own.
+pub fn is_error_page(key: &str) -> bool {
+    key == "error" || (key.len() == 3 && key.starts_with(['4', '5']) && key.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Page keys of the custom error pages for `status`, most specific first.
+pub fn error_page_candidates(status: u16) -> [String; 2] {
+    [status.to_string(), "error".to_string()]
+}
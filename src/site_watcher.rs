@@ -0,0 +1,62 @@
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+
+use crate::site_cache::{PathChange, SiteCache};
+
+// Editors often write a file several times per save; wait for them to settle
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Keeps the cache in sync with the pages directory while it is alive.
+pub struct SiteWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl SiteWatcher {
+    pub fn start(sites_dir: PathBuf, cache: Arc<RwLock<SiteCache>>) -> Result<Self, notify_debouncer_mini::notify::Error> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<DebounceEventResult>();
+        let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| {
+            let _ = tx.send(result);
+        })?;
+        debouncer.watcher().watch(&sites_dir, RecursiveMode::Recursive)?;
+
+        tokio::spawn(async move {
+            while let Some(result) = rx.recv().await {
+                match result {
+                    Ok(events) => {
+                        // Files are read before the lock is taken, so requests
+                        // keep being served from the old cache meanwhile
+                        let dir = sites_dir.clone();
+                        let changes = tokio::task::spawn_blocking(move || {
+                            events
+                                .iter()
+                                .map(|event| {
+                                    println!("Changed: {}", event.path.display());
+                                    SiteCache::read_change(&dir, &event.path)
+                                })
+                                .collect::<Vec<PathChange>>()
+                        });
+                        let changes = match changes.await {
+                            Ok(changes) => changes,
+                            Err(e) => {
+                                eprintln!("Error reading changed pages: {}", e);
+                                continue;
+                            }
+                        };
+
+                        let mut cache = cache.write().await;
+                        for change in changes {
+                            cache.apply_change(&sites_dir, change);
+                        }
+                    }
+                    Err(e) => eprintln!("Error watching pages directory: {}", e),
+                }
+            }
+        });
+
+        Ok(Self { _debouncer: debouncer })
+    }
+}
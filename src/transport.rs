@@ -0,0 +1,134 @@
+// Message transport between browsers and servers.
+//
+// The server and the browser only need to send a message to an address,
+// receive messages, and answer a message through the reply tag it came
+// with. `NymTransport` does this over the mixnet; tests use the in-process
+// network in `memory_transport`.
+
+use nym_sdk::mixnet::{self, AnonymousSenderTag, IncludedSurbs, MixnetMessageSender};
+use std::fmt;
+use std::future::Future;
+
+// Reply SURBs attached to every message so the receiver can answer anonymously
+pub const REPLY_SURBS: u32 = 10;
+
+#[derive(Debug, Clone)]
+pub struct Incoming<Tag> {
+    pub message: Vec<u8>,
+    /// Lets the receiver answer without learning the sender's address
+    pub reply_tag: Option<Tag>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportError(pub String);
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+pub trait Transport: Send + Sync {
+    type ReplyTag: Clone + fmt::Debug + Send + Sync + 'static;
+    type Sender: TransportSender<ReplyTag = Self::ReplyTag>;
+
+    /// Address other endpoints send to
+    // The browser takes its address from the client directly
+    #[allow(dead_code)]
+    fn address(&self) -> String;
+
+    /// Handle for sending while another task is receiving
+    fn sender(&self) -> Self::Sender;
+
+    /// Waits for the next batch of messages; `None` once the transport is closed.
+    fn receive(&mut self) -> impl Future<Output = Option<Vec<Incoming<Self::ReplyTag>>>> + Send;
+
+    /// Disconnects, e.g. before connecting again through another gateway.
+    // Only the browser reconnects
+    #[allow(dead_code)]
+    fn close(self) -> impl Future<Output = ()> + Send
+    where
+        Self: Sized;
+}
+
+pub trait TransportSender: Clone + Send + Sync + 'static {
+    type ReplyTag;
+
+    /// Sends `message` to `address` along with the means to reply to it.
+    fn send(&self, address: &str, message: Vec<u8>) -> impl Future<Output = Result<(), TransportError>> + Send;
+
+    /// Answers a message received with `tag`.
+    // Only the server answers through reply tags
+    #[allow(dead_code)]
+    fn reply(&self, tag: &Self::ReplyTag, message: Vec<u8>) -> impl Future<Output = Result<(), TransportError>> + Send;
+}
+
+pub struct NymTransport {
+    client: mixnet::MixnetClient,
+    #[allow(dead_code)]
+    address: String,
+}
+
+impl NymTransport {
+    pub fn new(client: mixnet::MixnetClient) -> Self {
+        let address = client.nym_address().to_string();
+        Self { client, address }
+    }
+}
+
+impl Transport for NymTransport {
+    type ReplyTag = AnonymousSenderTag;
+    type Sender = NymSender;
+
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    fn sender(&self) -> NymSender {
+        NymSender(self.client.split_sender())
+    }
+
+    async fn receive(&mut self) -> Option<Vec<Incoming<AnonymousSenderTag>>> {
+        let messages = self.client.wait_for_messages().await?;
+        Some(
+            messages
+                .into_iter()
+                .map(|received| Incoming {
+                    message: received.message,
+                    reply_tag: received.sender_tag,
+                })
+                .collect(),
+        )
+    }
+
+    async fn close(self) {
+        self.client.disconnect().await;
+    }
+}
+
+#[derive(Clone)]
+pub struct NymSender(mixnet::MixnetClientSender);
+
+impl TransportSender for NymSender {
+    type ReplyTag = AnonymousSenderTag;
+
+    async fn send(&self, address: &str, message: Vec<u8>) -> Result<(), TransportError> {
+        let recipient: mixnet::Recipient = address
+            .trim()
+            .parse()
+            .map_err(|e| TransportError(format!("Invalid address - {}", e)))?;
+        self.0
+            .send_message(recipient, message, IncludedSurbs::new(REPLY_SURBS))
+            .await
+            .map_err(|e| TransportError(e.to_string()))
+    }
+
+    async fn reply(&self, tag: &AnonymousSenderTag, message: Vec<u8>) -> Result<(), TransportError> {
+        self.0
+            .send_reply(*tag, message)
+            .await
+            .map_err(|e| TransportError(e.to_string()))
+    }
+}
@@ -0,0 +1,244 @@
+// Concurrent request handling.
+//
+// The receive loop only queues incoming messages. A dispatcher hands them to
+// at most `workers` concurrent tasks, and a single sender task writes all
+// responses back to the transport, so a slow send never holds up a worker
+// that could be reading the cache.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Semaphore};
+
+use crate::mixnet_server::ServerState;
+use crate::protocol::{Response, Status};
+use crate::transport::TransportSender;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Requests processed at the same time
+    pub workers: usize,
+    /// Requests waiting for a worker before new ones are turned away
+    pub queue_size: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            workers: 8,
+            queue_size: 256,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PoolMetrics {
+    queued: AtomicUsize,
+    peak_queued: AtomicUsize,
+    active: AtomicUsize,
+    processed: AtomicU64,
+    rejected: AtomicU64,
+    rate_limited: AtomicU64,
+    challenged: AtomicU64,
+    unsent: AtomicUsize,
+}
+
+impl PoolMetrics {
+    pub fn processed(&self) -> u64 {
+        self.processed.load(Ordering::Relaxed)
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    pub fn count_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_challenged(&self) {
+        self.challenged.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "queued {} (peak {}), active {}, awaiting send {}, processed {}, rejected as busy {}, rate limited {}, challenged {}",
+            self.queued.load(Ordering::Relaxed),
+            self.peak_queued.load(Ordering::Relaxed),
+            self.active.load(Ordering::Relaxed),
+            self.unsent.load(Ordering::Relaxed),
+            self.processed.load(Ordering::Relaxed),
+            self.rejected.load(Ordering::Relaxed),
+            self.rate_limited.load(Ordering::Relaxed),
+            self.challenged.load(Ordering::Relaxed),
+        )
+    }
+}
+
+struct Job<Tag> {
+    // Index of the site in `ServerState::sites`
+    site: usize,
+    message: Vec<u8>,
+    reply_tag: Option<Tag>,
+}
+
+// Encoded response chunks on their way back to a visitor
+struct Outgoing<Tag> {
+    site: usize,
+    chunks: Vec<Vec<u8>>,
+    reply_tag: Option<Tag>,
+    reply_to: Option<String>,
+}
+
+pub struct WorkerPool<Tag> {
+    jobs: mpsc::Sender<Job<Tag>>,
+    outgoing: mpsc::Sender<Outgoing<Tag>>,
+    state: Arc<ServerState>,
+}
+
+impl<Tag: Clone + Send + Sync + 'static> WorkerPool<Tag> {
+    /// Starts the workers for all sites; `senders` holds each site's transport sender.
+    pub fn start<S: TransportSender<ReplyTag = Tag>>(config: PoolConfig, state: Arc<ServerState>, senders: Vec<S>) -> Self {
+        let (jobs, job_rx) = mpsc::channel(config.queue_size.max(1));
+        let (outgoing, outgoing_rx) = mpsc::channel(config.queue_size.max(1));
+
+        tokio::spawn(Self::dispatch(config.workers.max(1), job_rx, outgoing.clone(), state.clone()));
+        tokio::spawn(Self::send_responses(outgoing_rx, senders, state.clone()));
+        if let Some(interval) = state.logging.metrics_interval {
+            tokio::spawn(Self::log_metrics(state.clone(), interval));
+        }
+
+        Self { jobs, outgoing, state }
+    }
+
+    /// Queues a received message, answering "busy" right away if the queue is full.
+    pub fn submit(&self, site: usize, message: Vec<u8>, reply_tag: Option<Tag>) {
+        let metrics = &self.state.metrics;
+        // Counted before sending: the dispatcher may take the job, and count
+        // it down, before try_send even returns
+        let queued = metrics.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        match self.jobs.try_send(Job { site, message, reply_tag }) {
+            Ok(()) => {
+                metrics.peak_queued.fetch_max(queued, Ordering::Relaxed);
+            }
+            Err(mpsc::error::TrySendError::Full(job)) => {
+                metrics.queued.fetch_sub(1, Ordering::Relaxed);
+                metrics.rejected.fetch_add(1, Ordering::Relaxed);
+                // The queue is shared, so every site is overloaded
+                for site in &self.state.sites {
+                    site.admission.note_overload();
+                }
+                self.reject(site, &job.message, job.reply_tag, |id| {
+                    Response::error(id, Status::Unavailable, "Server busy, try again later")
+                });
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                metrics.queued.fetch_sub(1, Ordering::Relaxed);
+                eprintln!("Worker pool stopped, dropping request");
+            }
+        }
+    }
+
+    /// Answers a message with the response `make_response` builds for its
+    /// request id, without queueing it for a worker.
+    ///
+    /// Best effort: nothing is sent if the sender task is already behind.
+    pub fn reject(&self, site: usize, message: &[u8], reply_tag: Option<Tag>, make_response: impl FnOnce(u64) -> Response) {
+        // Checked before the answer is built and signed, so a flood of
+        // requests that only get rejected costs no signatures once we're behind
+        if self.outgoing.capacity() == 0 {
+            return;
+        }
+        let Some((chunks, reply_to)) = self.state.reject_request(site, message, make_response) else {
+            return;
+        };
+        let outgoing = Outgoing { site, chunks, reply_tag, reply_to };
+        // Counted up front for the same reason as `queued` in `submit`
+        self.state.metrics.unsent.fetch_add(1, Ordering::Relaxed);
+        if self.outgoing.try_send(outgoing).is_err() {
+            self.state.metrics.unsent.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    async fn dispatch(
+        workers: usize,
+        mut job_rx: mpsc::Receiver<Job<Tag>>,
+        outgoing: mpsc::Sender<Outgoing<Tag>>,
+        state: Arc<ServerState>,
+    ) {
+        let slots = Arc::new(Semaphore::new(workers));
+
+        while let Some(job) = job_rx.recv().await {
+            let Ok(slot) = slots.clone().acquire_owned().await else {
+                break;
+            };
+            state.metrics.queued.fetch_sub(1, Ordering::Relaxed);
+            state.metrics.active.fetch_add(1, Ordering::Relaxed);
+
+            let outgoing = outgoing.clone();
+            let state = state.clone();
+            tokio::spawn(async move {
+                let response = state.handle_request(job.site, &job.message).await;
+                state.metrics.active.fetch_sub(1, Ordering::Relaxed);
+                state.metrics.processed.fetch_add(1, Ordering::Relaxed);
+
+                if let Some((chunks, reply_to)) = response {
+                    state.metrics.unsent.fetch_add(1, Ordering::Relaxed);
+                    // Keeps the worker slot while the sender task is behind, so the
+                    // backlog shows up in the queue instead of growing here
+                    let outgoing_response = Outgoing { site: job.site, chunks, reply_tag: job.reply_tag, reply_to };
+                    if outgoing.send(outgoing_response).await.is_err() {
+                        state.metrics.unsent.fetch_sub(1, Ordering::Relaxed);
+                    }
+                }
+                drop(slot);
+            });
+        }
+    }
+
+    async fn send_responses<S: TransportSender<ReplyTag = Tag>>(
+        mut outgoing_rx: mpsc::Receiver<Outgoing<Tag>>,
+        senders: Vec<S>,
+        state: Arc<ServerState>,
+    ) {
+        while let Some(outgoing) = outgoing_rx.recv().await {
+            state.metrics.unsent.fetch_sub(1, Ordering::Relaxed);
+            // Answers go out through the identity of the site that was asked
+            let sender = &senders[outgoing.site];
+
+            // Prefer the anonymous reply SURBs; the visitor's address stays unknown to us
+            if let Some(reply_tag) = outgoing.reply_tag {
+                for chunk in outgoing.chunks {
+                    if let Err(e) = sender.reply(&reply_tag, chunk).await {
+                        eprintln!("Error sending response: {}", e);
+                        break;
+                    }
+                }
+            } else if let Some(target) = outgoing.reply_to {
+                eprintln!("Deprecated: request without reply SURBs, answering its FROM address");
+                for chunk in outgoing.chunks {
+                    if let Err(e) = sender.send(&target, chunk).await {
+                        eprintln!("Error sending response: {}", e);
+                        break;
+                    }
+                }
+            } else {
+                eprintln!("Request carried neither reply SURBs nor a response address");
+            }
+        }
+    }
+
+    // Writes the metrics to the log every `interval` while there is traffic
+    async fn log_metrics(state: Arc<ServerState>, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        let mut last_processed = 0;
+        loop {
+            interval.tick().await;
+            let processed = state.metrics.processed();
+            if processed != last_processed {
+                println!("Workers: {}", state.metrics.summary());
+                last_processed = processed;
+            }
+        }
+    }
+}
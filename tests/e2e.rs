@@ -0,0 +1,621 @@
+// End-to-end tests: a real server and a test client talking over the
+// in-process network from `memory_transport`.
+
+use ed25519_zebra::SigningKey;
+use std::fs;
+use std::time::Duration;
+use tempfile::TempDir;
+
+use nymview::admin;
+use nymview::admission::{self, AdmissionConfig};
+use nymview::chunking::{self, ChunkAssembler};
+use nymview::config::{self, ServerConfig, Settings};
+use nymview::default_page;
+use nymview::gateway;
+use nymview::memory_transport::{MemoryNetwork, MemoryTransport};
+use nymview::mixnet_server::NymMixnetServer;
+use nymview::pow::{self, Challenge};
+use nymview::protocol::{self, Request, Response, Status};
+use nymview::rate_limit::{BucketConfig, RateLimitConfig};
+use nymview::signing::{self, SignatureStatus};
+use nymview::transport::{Transport, TransportSender};
+use nymview::worker_pool::PoolConfig;
+
+const SERVER: &str = "server";
+
+// A request is repeated, asking only for missing chunks, after this much silence
+const ANSWER_TIMEOUT: Duration = Duration::from_millis(500);
+const MAX_ATTEMPTS: u32 = 20;
+
+// Proof of work kept cheap enough for debug builds
+fn test_admission() -> AdmissionConfig {
+    AdmissionConfig {
+        difficulty: 8,
+        ..AdmissionConfig::default()
+    }
+}
+
+struct TestSite {
+    pages: TempDir,
+    identity: TempDir,
+    admin_key: SigningKey,
+}
+
+impl TestSite {
+    fn new() -> Self {
+        let identity = TempDir::new().unwrap();
+        let admin_key = SigningKey::new(rand::thread_rng());
+        fs::write(
+            identity.path().join("admin_keys"),
+            format!("# test admin\n{}\n", signing::public_key_hex(&admin_key)),
+        )
+        .unwrap();
+
+        Self {
+            pages: TempDir::new().unwrap(),
+            identity,
+            admin_key,
+        }
+    }
+
+    fn write(&self, relative: &str, contents: impl AsRef<[u8]>) {
+        let path = self.pages.path().join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    /// Default settings for serving the site, with cheap proof of work.
+    fn config(&self) -> ServerConfig {
+        let settings = Settings {
+            pages_dir: Some(self.pages.path().to_path_buf()),
+            identity_dir: Some(self.identity.path().to_path_buf()),
+            ..Settings::default()
+        };
+        ServerConfig {
+            admission: test_admission(),
+            ..settings.resolve(config::DEFAULT_PROFILE)
+        }
+    }
+
+    /// Starts a server for the site on `network` and returns its public site key.
+    async fn serve(&self, network: &MemoryNetwork) -> String {
+        start_server(network, self.config()).await
+    }
+}
+
+async fn start_server(network: &MemoryNetwork, config: ServerConfig) -> String {
+    let server = NymMixnetServer::with_transport(network.endpoint(SERVER), &config)
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let _ = server.start().await;
+    });
+
+    public_site_key(&config)
+}
+
+fn public_site_key(config: &ServerConfig) -> String {
+    let site_key = fs::read_to_string(config.identity_dir.join("site_key")).unwrap();
+    signing::public_key_hex(&signing::parse_secret_key(&site_key).unwrap())
+}
+
+struct TestClient {
+    transport: MemoryTransport,
+    server: String,
+}
+
+impl TestClient {
+    fn new(network: &MemoryNetwork, address: &str) -> Self {
+        Self::of(network, address, SERVER)
+    }
+
+    /// A client visiting the server at `server` instead of the default one.
+    fn of(network: &MemoryNetwork, address: &str, server: &str) -> Self {
+        Self {
+            transport: network.endpoint(address),
+            server: server.to_string(),
+        }
+    }
+
+    async fn get(&mut self, path: &str) -> Response {
+        self.request(Request::new(rand::random(), "GET", path)).await
+    }
+
+    /// Sends a request and reassembles its answer, asking again for lost chunks
+    /// and solving proof-of-work challenges like the browser.
+    async fn request(&mut self, mut request: Request) -> Response {
+        let sender = self.transport.sender();
+        let mut assembler: Option<ChunkAssembler> = None;
+        let mut attempt = request.clone();
+
+        'attempts: for _ in 0..MAX_ATTEMPTS {
+            sender.send(&self.server, attempt.encode().unwrap()).await.unwrap();
+
+            while let Ok(Some(messages)) = tokio::time::timeout(ANSWER_TIMEOUT, self.transport.receive()).await {
+                for incoming in messages {
+                    let response = Response::decode(&incoming.message).unwrap();
+                    if response.id != request.id {
+                        continue;
+                    }
+                    if assembler.is_none() && response.status == Status::ChallengeRequired {
+                        let challenge = Challenge::from_response(&response).unwrap();
+                        let nonce = pow::solve(&challenge, &request);
+                        pow::attach(&mut request, &challenge, nonce);
+                        attempt = request.clone();
+                        continue 'attempts;
+                    }
+                    let current = match assembler.as_mut() {
+                        Some(current) => current,
+                        None => assembler.insert(ChunkAssembler::new(&response).unwrap()),
+                    };
+                    current.insert(response).unwrap();
+                    if current.is_complete() {
+                        return assembler.take().unwrap().finish(request.id).unwrap();
+                    }
+                }
+            }
+
+            attempt = request.clone();
+            if let Some(current) = &assembler {
+                attempt.headers.push((
+                    chunking::HEADER_RESEND_CHUNKS.to_string(),
+                    chunking::format_resend_list(&current.missing()),
+                ));
+            }
+        }
+
+        panic!("no complete answer to {} {}", request.command, request.path);
+    }
+}
+
+#[tokio::test]
+async fn serves_default_index() {
+    let site = TestSite::new();
+    let network = MemoryNetwork::new();
+    site.serve(&network).await;
+    let mut client = TestClient::new(&network, "client");
+
+    let response = client.get("/").await;
+    assert_eq!(response.status, Status::Ok);
+    assert_eq!(response.header(protocol::HEADER_CONTENT_TYPE), Some(protocol::MARKDOWN_MIME));
+    assert_eq!(response.body_text(), default_page::default_index());
+}
+
+#[tokio::test]
+async fn serves_nested_pages_and_assets() {
+    let site = TestSite::new();
+    site.write("index.md", "# Home");
+    site.write("blog/post1.md", "# First post");
+    site.write("blog/index.markdown", "# Blog");
+    site.write("img/logo.png", [0x89, b'P', b'N', b'G']);
+    let network = MemoryNetwork::new();
+    site.serve(&network).await;
+    let mut client = TestClient::new(&network, "client");
+
+    assert_eq!(client.get("/").await.body_text(), "# Home");
+    assert_eq!(client.get("/blog/post1").await.body_text(), "# First post");
+    assert_eq!(client.get("/blog/").await.body_text(), "# Blog");
+
+    let logo = client.get("/img/logo.png").await;
+    assert_eq!(logo.status, Status::Ok);
+    assert_eq!(logo.header(protocol::HEADER_CONTENT_TYPE), Some("image/png"));
+    assert_eq!(logo.body, vec![0x89, b'P', b'N', b'G']);
+}
+
+#[tokio::test]
+async fn rejects_missing_and_invalid_paths() {
+    let site = TestSite::new();
+    let network = MemoryNetwork::new();
+    site.serve(&network).await;
+    let mut client = TestClient::new(&network, "client");
+
+    assert_eq!(client.get("/nothing/here").await.status, Status::NotFound);
+    assert_eq!(client.get("/../secret").await.status, Status::BadRequest);
+    let unknown = client.request(Request::new(rand::random(), "DELETE", "/")).await;
+    assert_eq!(unknown.status, Status::BadRequest);
+}
+
+#[tokio::test]
+async fn failed_requests_get_the_sites_error_pages() {
+    let site = TestSite::new();
+    site.write("404.md", "# Lost\n\nNothing at {{path}}");
+    site.write("error.md", "# Error {{status}}\n\n{{message}}");
+    let network = MemoryNetwork::new();
+    site.serve(&network).await;
+    let mut client = TestClient::new(&network, "client");
+
+    let missing = client.get("/nothing/[here](elsewhere)").await;
+    assert_eq!(missing.status, Status::NotFound);
+    assert_eq!(missing.header(protocol::HEADER_CONTENT_TYPE), Some(protocol::MARKDOWN_MIME));
+    assert_eq!(
+        missing.body_text(),
+        "# Lost\n\nNothing at /nothing/\\[here\\]\\(elsewhere\\)"
+    );
+
+    let invalid = client.get("/../secret").await;
+    assert_eq!(invalid.status, Status::BadRequest);
+    assert!(invalid.body_text().starts_with("# Error 400"), "{}", invalid.body_text());
+
+    // Error pages are not pages of their own
+    let direct = client.get("/404").await;
+    assert_eq!(direct.status, Status::NotFound);
+    assert_eq!(client.get("/error").await.status, Status::NotFound);
+
+    // Only page requests get them, other commands keep their plain answers
+    let unknown = client.request(Request::new(rand::random(), "DELETE", "/")).await;
+    assert_eq!(unknown.body_text(), "Unknown command: DELETE");
+}
+
+#[tokio::test]
+async fn moved_pages_answer_with_redirects() {
+    let site = TestSite::new();
+    site.write(
+        "redirects.toml",
+        "\"old-post\" = \"/blog/post1\"\n\"docs/\" = \"/manual/\"\n\"away\" = \"nym://other.site/docs\"\n\"kept\" = \"/blog/post1\"\n",
+    );
+    site.write("blog/post1.md", "---\ntitle: First\naliases: [2023/post, /first]\n---\n# First post");
+    site.write("ruled.md", "---\n# Ruled\n---\nText");
+    site.write("kept.md", "# Still here");
+    site.write("error.md", "# Error {{status}}");
+    let network = MemoryNetwork::new();
+    site.serve(&network).await;
+    let mut client = TestClient::new(&network, "client");
+
+    let location = |response: Response| {
+        assert_eq!(response.status, Status::Redirect);
+        response.header(protocol::HEADER_LOCATION).unwrap().to_string()
+    };
+    assert_eq!(location(client.get("/old-post").await), "/blog/post1");
+    assert_eq!(location(client.get("/docs").await), "/manual/");
+    assert_eq!(location(client.get("/docs/").await), "/manual/");
+    assert_eq!(location(client.get("/away").await), "nym://other.site/docs");
+    assert_eq!(location(client.get("/first").await), "/blog/post1");
+    assert_eq!(location(client.get("/2023/post/").await), "/blog/post1");
+
+    // Front matter is not part of the page, a leading rule is
+    assert_eq!(client.get("/blog/post1").await.body_text(), "# First post");
+    assert_eq!(client.get("/ruled").await.body_text(), "---\n# Ruled\n---\nText");
+    // Pages that exist win over redirects
+    assert_eq!(client.get("/kept").await.body_text(), "# Still here");
+}
+
+#[tokio::test]
+async fn responses_are_signed_by_the_site_key() {
+    let site = TestSite::new();
+    site.write("index.md", "# Signed");
+    let network = MemoryNetwork::new();
+    let site_key = site.serve(&network).await;
+    let mut client = TestClient::new(&network, "client");
+
+    let mut response = client.get("/").await;
+    assert_eq!(signing::verify_response(&response), SignatureStatus::Verified(site_key));
+
+    response.body = b"# Tampered".to_vec();
+    assert!(matches!(signing::verify_response(&response), SignatureStatus::Invalid(_)));
+}
+
+#[tokio::test]
+async fn large_asset_survives_a_lossy_network() {
+    let site = TestSite::new();
+    let data: Vec<u8> = (0..10 * chunking::CHUNK_SIZE + 123).map(|i| (i % 251) as u8).collect();
+    site.write("files/archive.zip", &data);
+    let network = MemoryNetwork::new()
+        .with_latency(Duration::from_millis(10))
+        .with_jitter(Duration::from_millis(30))
+        .with_loss(0.15)
+        .with_seed(7);
+    let site_key = site.serve(&network).await;
+    let mut client = TestClient::new(&network, "client");
+
+    let response = client.get("/files/archive.zip").await;
+    assert_eq!(response.status, Status::Ok);
+    assert_eq!(response.body, data);
+    assert_eq!(signing::verify_response(&response), SignatureStatus::Verified(site_key));
+}
+
+#[tokio::test]
+async fn reload_requires_a_signed_admin_request() {
+    let site = TestSite::new();
+    let network = MemoryNetwork::new();
+    site.serve(&network).await;
+    let mut client = TestClient::new(&network, "client");
+
+    let unsigned = client.request(Request::new(rand::random(), "RELOAD", "")).await;
+    assert_eq!(unsigned.status, Status::Unauthorized);
+
+    let mut stranger = Request::new(rand::random(), "RELOAD", "");
+    admin::sign_request(&mut stranger, &SigningKey::new(rand::thread_rng()), SERVER);
+    assert_eq!(client.request(stranger).await.status, Status::Unauthorized);
+
+    site.write("new.md", "# New");
+    let mut signed = Request::new(rand::random(), "RELOAD", "");
+    admin::sign_request(&mut signed, &site.admin_key, SERVER);
+    let reloaded = client.request(signed.clone()).await;
+    assert_eq!(reloaded.status, Status::Ok, "{}", reloaded.body_text());
+    assert_eq!(client.get("/new").await.body_text(), "# New");
+
+    // The same signed request cannot be played back
+    signed.id = rand::random();
+    assert_eq!(client.request(signed).await.status, Status::Unauthorized);
+}
+
+#[tokio::test]
+async fn concurrent_clients_get_their_own_answers() {
+    let site = TestSite::new();
+    site.write("alice.md", "# Alice");
+    site.write("bob.md", "# Bob");
+    let network = MemoryNetwork::new().with_latency(Duration::from_millis(5));
+    site.serve(&network).await;
+    let mut alice = TestClient::new(&network, "alice");
+    let mut bob = TestClient::new(&network, "bob");
+
+    let (for_alice, for_bob) = tokio::join!(alice.get("/alice"), bob.get("/bob"));
+    assert_eq!(for_alice.body_text(), "# Alice");
+    assert_eq!(for_bob.body_text(), "# Bob");
+}
+
+#[tokio::test]
+async fn full_queue_turns_requests_away() {
+    let site = TestSite::new();
+    let network = MemoryNetwork::new();
+    let config = ServerConfig {
+        pool: PoolConfig { workers: 1, queue_size: 2 },
+        // Without proof of work, which would otherwise take over once the queue is full
+        admission: AdmissionConfig { difficulty: 0, ..test_admission() },
+        ..site.config()
+    };
+    start_server(&network, config).await;
+    let mut client = TestClient::new(&network, "client");
+
+    // Delivered in one batch, faster than the single worker can take them
+    let sender = client.transport.sender();
+    for id in 0..10u64 {
+        let request = Request::new(id, "PING", "");
+        sender.send(SERVER, request.encode().unwrap()).await.unwrap();
+    }
+
+    let mut statuses = Vec::new();
+    while let Ok(Some(messages)) = tokio::time::timeout(ANSWER_TIMEOUT, client.transport.receive()).await {
+        for incoming in messages {
+            statuses.push(Response::decode(&incoming.message).unwrap().status);
+        }
+    }
+    // Busy answers are best effort, they must not pile up behind real responses
+    let served = statuses.iter().filter(|status| **status == Status::Ok).count();
+    assert_eq!(served, 2);
+    assert!(statuses.contains(&Status::Unavailable));
+
+    let mut stats = Request::new(rand::random(), "STATS", "");
+    admin::sign_request(&mut stats, &site.admin_key, SERVER);
+    let stats = client.request(stats).await.body_text();
+    assert!(stats.contains("rejected as busy 8"), "{}", stats);
+}
+
+/// Sends `count` pings at once and returns the statuses of the answers.
+async fn ping_burst(client: &mut TestClient, count: u64) -> Vec<Response> {
+    let sender = client.transport.sender();
+    for id in 0..count {
+        sender.send(SERVER, Request::new(id, "PING", "").encode().unwrap()).await.unwrap();
+    }
+
+    let mut responses = Vec::new();
+    while let Ok(Some(messages)) = tokio::time::timeout(ANSWER_TIMEOUT, client.transport.receive()).await {
+        responses.extend(messages.iter().map(|incoming| Response::decode(&incoming.message).unwrap()));
+    }
+    responses
+}
+
+#[tokio::test]
+async fn senders_over_their_limit_are_told_to_slow_down() {
+    let site = TestSite::new();
+    let network = MemoryNetwork::new();
+    let rate_limits = RateLimitConfig {
+        per_sender: BucketConfig::new(1.0, 3.0),
+        global: None,
+        message: "Easy there".to_string(),
+    };
+    start_server(&network, ServerConfig { rate_limits, ..site.config() }).await;
+    let mut alice = TestClient::new(&network, "alice");
+    let mut bob = TestClient::new(&network, "bob");
+
+    let responses = ping_burst(&mut alice, 5).await;
+    assert_eq!(responses.len(), 5);
+    let limited: Vec<&Response> = responses
+        .iter()
+        .filter(|response| response.status == Status::RateLimited)
+        .collect();
+    assert_eq!(limited.len(), 2);
+    assert_eq!(limited[0].body_text(), "Easy there");
+    assert_eq!(limited[0].header(protocol::HEADER_RETRY_AFTER), Some("1"));
+
+    // Someone else is not affected
+    assert_eq!(bob.request(Request::new(rand::random(), "PING", "")).await.body_text(), "PONG");
+
+    // After the hinted wait the sender is served again
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(alice.request(Request::new(rand::random(), "PING", "")).await.body_text(), "PONG");
+}
+
+#[tokio::test]
+async fn global_limit_covers_all_senders() {
+    let site = TestSite::new();
+    let network = MemoryNetwork::new();
+    let rate_limits = RateLimitConfig {
+        per_sender: None,
+        global: BucketConfig::new(0.1, 2.0),
+        ..RateLimitConfig::default()
+    };
+    start_server(&network, ServerConfig { rate_limits, ..site.config() }).await;
+    let mut alice = TestClient::new(&network, "alice");
+    let mut bob = TestClient::new(&network, "bob");
+
+    let for_alice = ping_burst(&mut alice, 2).await;
+    assert!(for_alice.iter().all(|response| response.is_ok()));
+    let for_bob = ping_burst(&mut bob, 1).await;
+    assert_eq!(for_bob[0].status, Status::RateLimited);
+}
+
+#[tokio::test]
+async fn overloaded_server_asks_for_proof_of_work() {
+    let site = TestSite::new();
+    let network = MemoryNetwork::new();
+    let config = ServerConfig {
+        pool: PoolConfig { workers: 1, queue_size: 16 },
+        admission: AdmissionConfig { queue_threshold: 2, ..test_admission() },
+        ..site.config()
+    };
+    start_server(&network, config).await;
+    let mut client = TestClient::new(&network, "client");
+
+    // The queue reaches the threshold with the second ping, the rest must solve a challenge
+    let responses = ping_burst(&mut client, 10).await;
+    assert_eq!(responses.len(), 10);
+    assert_eq!(responses.iter().filter(|response| response.is_ok()).count(), 2);
+    let challenged: Vec<&Response> = responses
+        .iter()
+        .filter(|response| response.status == Status::ChallengeRequired)
+        .collect();
+    assert_eq!(challenged.len(), 8);
+    let challenge = Challenge::from_response(challenged[0]).unwrap();
+    assert_eq!(challenge.difficulty, 8);
+
+    // Solving it gets the request through
+    let pong = client.request(Request::new(rand::random(), "PING", "")).await;
+    assert_eq!(pong.body_text(), "PONG");
+
+    // A solution admits its request and a few resends, not a flood of copies
+    let mut solved = Request::new(rand::random(), "PING", "");
+    let nonce = pow::solve(&challenge, &solved);
+    pow::attach(&mut solved, &challenge, nonce);
+    let sender = client.transport.sender();
+    for _ in 0..=admission::MAX_TICKET_USES {
+        sender.send(SERVER, solved.encode().unwrap()).await.unwrap();
+    }
+    let mut statuses = Vec::new();
+    while let Ok(Some(messages)) = tokio::time::timeout(ANSWER_TIMEOUT, client.transport.receive()).await {
+        statuses.extend(messages.iter().map(|incoming| Response::decode(&incoming.message).unwrap().status));
+    }
+    statuses.sort_by_key(|status| status.code());
+    let mut expected = vec![Status::Ok; admission::MAX_TICKET_USES as usize];
+    expected.push(Status::ChallengeRequired);
+    assert_eq!(statuses, expected);
+}
+
+#[tokio::test]
+async fn serves_a_profile_from_a_config_file() {
+    let dir = TempDir::new().unwrap();
+    let config_file = dir.path().join("server.toml");
+    fs::write(
+        &config_file,
+        "[profiles.blog]\npages_dir = \"blog\"\nidentity_dir = \"blog-identity\"\n",
+    )
+    .unwrap();
+    fs::create_dir_all(dir.path().join("blog")).unwrap();
+    fs::write(dir.path().join("blog/index.md"), "# Blog").unwrap();
+
+    let blog = Settings::load(Some(&config_file), "blog").unwrap().resolve("blog");
+    let network = MemoryNetwork::new();
+    start_server(&network, blog).await;
+    let mut client = TestClient::new(&network, "client");
+    assert_eq!(client.get("/").await.body_text(), "# Blog");
+    assert!(dir.path().join("blog-identity/site_key").exists());
+}
+
+#[tokio::test]
+async fn the_gateway_is_remembered_next_to_the_site_keys() {
+    const GATEWAY: &str = "E3mvZTHQCdBvhfr178Swx9g4QG3kkRUun7YnToLMcMbM";
+
+    let site = TestSite::new();
+    let network = MemoryNetwork::new();
+    let address = format!("client.encryption@{}", GATEWAY);
+    NymMixnetServer::with_transport(network.endpoint(&address), &site.config()).await.unwrap();
+    let remembered = gateway::load(&site.identity.path().join("gateway")).unwrap();
+    assert_eq!(remembered, vec![GATEWAY.to_string()]);
+}
+
+#[tokio::test]
+async fn one_process_hosts_several_sites() {
+    let blog = TestSite::new();
+    blog.write("index.md", "# Blog");
+    let docs = TestSite::new();
+    docs.write("index.md", "# Docs");
+    let network = MemoryNetwork::new();
+    // The docs site also trusts the blog's admin
+    fs::write(
+        docs.identity.path().join("admin_keys"),
+        format!(
+            "{}\n{}\n",
+            signing::public_key_hex(&docs.admin_key),
+            signing::public_key_hex(&blog.admin_key)
+        ),
+    )
+    .unwrap();
+
+    let config = |site: &TestSite, profile: &str| ServerConfig {
+        profile: profile.to_string(),
+        ..site.config()
+    };
+    let (blog_config, docs_config) = (config(&blog, "blog"), config(&docs, "docs"));
+    let server = NymMixnetServer::with_transports(vec![
+        (network.endpoint("blog"), blog_config.clone()),
+        (network.endpoint("docs"), docs_config.clone()),
+    ])
+    .await
+    .unwrap();
+    let blog_key = public_site_key(&blog_config);
+    let docs_key = public_site_key(&docs_config);
+    tokio::spawn(async move {
+        let _ = server.start().await;
+    });
+    assert_ne!(blog_key, docs_key);
+
+    // Each address serves its own pages, signed with its own key
+    let mut blog_client = TestClient::of(&network, "blog-reader", "blog");
+    let mut docs_client = TestClient::of(&network, "docs-reader", "docs");
+    let page = blog_client.get("/").await;
+    assert_eq!(page.body_text(), "# Blog");
+    assert_eq!(signing::verify_response(&page), SignatureStatus::Verified(blog_key));
+    let page = docs_client.get("/").await;
+    assert_eq!(page.body_text(), "# Docs");
+    assert_eq!(signing::verify_response(&page), SignatureStatus::Verified(docs_key));
+
+    // Admin keys only count for the sites that trust them
+    let mut foreign = Request::new(rand::random(), "RELOAD", "");
+    admin::sign_request(&mut foreign, &docs.admin_key, "blog");
+    assert_eq!(blog_client.request(foreign).await.status, Status::Unauthorized);
+    let mut own = Request::new(rand::random(), "RELOAD", "");
+    admin::sign_request(&mut own, &docs.admin_key, "docs");
+    assert_eq!(docs_client.request(own).await.status, Status::Ok);
+
+    // A request signed for one site cannot be played back to another
+    let mut for_blog = Request::new(rand::random(), "RELOAD", "");
+    admin::sign_request(&mut for_blog, &blog.admin_key, "blog");
+    assert_eq!(docs_client.request(for_blog.clone()).await.status, Status::Unauthorized);
+    assert_eq!(blog_client.request(for_blog).await.status, Status::Ok);
+    let mut for_docs = Request::new(rand::random(), "RELOAD", "");
+    admin::sign_request(&mut for_docs, &blog.admin_key, "docs");
+    assert_eq!(docs_client.request(for_docs).await.status, Status::Ok);
+
+    // Sites need their own identity
+    let twice = NymMixnetServer::with_transports(vec![
+        (network.endpoint("first"), blog.config()),
+        (network.endpoint("second"), blog.config()),
+    ])
+    .await;
+    assert!(twice.is_err());
+}
+
+#[tokio::test]
+async fn malformed_messages_do_not_stop_the_server() {
+    let site = TestSite::new();
+    let network = MemoryNetwork::new();
+    site.serve(&network).await;
+    let mut client = TestClient::new(&network, "client");
+
+    let sender = client.transport.sender();
+    sender.send(SERVER, b"not a request".to_vec()).await.unwrap();
+    sender.send(SERVER, Vec::new()).await.unwrap();
+
+    let pong = client.request(Request::new(rand::random(), "PING", "")).await;
+    assert_eq!(pong.body_text(), "PONG");
+}